@@ -9,36 +9,50 @@ use std::time::Instant;
 pub fn bench_separations(criterion: &mut Criterion) {
     let arguments = "-p sRGB -o benches/output.cube -c 102 51 153 -s 16 -t 10000";
 
+    // Each search structure, on secondary colors dense enough to tell them
+    // apart.
+    let arguments_search = ["rtree", "grid", "kdtree", "table"].map(|structure|
+        (structure, format!("-p sRGB -o benches/output.cube -c 0 255 255 -c 255 0 255 -c 255 255 0 -c 0 0 0 -s 33 -t 1000000 --search {}", structure))
+    );
+
     let mut group = criterion.benchmark_group("bench_separations");
     group.sample_size(100);
     group.measurement_time(Duration::from_secs(1000));
     
-    group.bench_function("bench_separations", |bencher| bencher.iter_custom(|iterations| {
-        let mut duration = Duration::new(0, 0);
+    group.bench_function("bench_separations", |bencher| bencher.iter_custom(|iterations| run(arguments, iterations)));
 
-        for _ in 0..iterations {
-            let mut process = Command::new("cargo");
-            process.args(["run", "--"]);
-            process.args(arguments.split(' '));
+    for (structure, arguments) in arguments_search.iter() {
+        group.bench_function(format!("bench_search_{}", structure), |bencher| bencher.iter_custom(|iterations| run(arguments, iterations)));
+    }
 
-            let start = Instant::now();
+    group.finish();
+}
 
-            let _ = process.output();
+/// Runs the program with the given arguments the given number of times,
+/// returning how long it took altogether.
+fn run(arguments: &str, iterations: u64) -> Duration {
+    let mut duration = Duration::new(0, 0);
 
-            duration += start.elapsed();
+    for _ in 0..iterations {
+        let mut process = Command::new("cargo");
+        process.args(["run", "--"]);
+        process.args(arguments.split(' '));
 
-            // Clean up
-            let _ = remove_file("benches/output.cube");
-            for index in 0..=10 {
-                let _ = remove_file(format!("benches/output_{}.cube", index));
-                let _ = remove_file(format!("benches/output_{}m.cube", index));
-            }
-        }
+        let start = Instant::now();
 
-        duration
-    }));
+        let _ = process.output();
 
-    group.finish();
+        duration += start.elapsed();
+
+        // Clean up
+        let _ = remove_file("benches/output.cube");
+        for index in 0..=10 {
+            let _ = remove_file(format!("benches/output_{}.cube", index));
+            let _ = remove_file(format!("benches/output_{}m.cube", index));
+        }
+    }
+
+    duration
 }
 
 criterion_group!(benches, bench_separations);
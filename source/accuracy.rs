@@ -0,0 +1,247 @@
+use crate::inks::Inks;
+use crate::mapping;
+use crate::mapping::Mapping;
+use crate::secondaries;
+use crate::secondaries::Lattice;
+
+/// The number of secondary colors that the search for a resolution meeting a
+/// tolerance starts measuring from.
+const TARGET_COARSE: usize = 4096;
+
+/// The ratio between the bounds of consecutive buckets of the histogram used to
+/// find percentiles of the quantization error, which is also the most that a
+/// reported percentile can overestimate the actual percentile by.
+const RATIO_BUCKET: f32 = 1.01;
+
+/// The smallest color difference the histogram tells apart from 0.
+const DIFFERENCE_MIN: f32 = 1e-6;
+
+/// The largest color difference the histogram tells apart from larger ones.
+const DIFFERENCE_MAX: f32 = 1e3;
+
+/// The largest number of samples the search for a resolution meeting a
+/// tolerance will measure. Each secondary color takes 20 bytes in a store and
+/// more in memory, so this is already a few gigabytes' worth, and over twice
+/// the default target number.
+pub const COUNT_MAX: u128 = 1 << 28;
+
+/// A statistic summarizing the quantization error of a sampling of ink
+/// fractions: the CIE76 color differences between the mixtures of adjacent
+/// samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    /// The largest color difference.
+    Maximum,
+    /// The 95th percentile of the color differences.
+    Percentile95,
+}
+
+/// Returns the given statistic of the quantization error of the given inks
+/// sampled on the given lattice, counting only samples that respect the ink
+/// limit, sampling across the given number of threads.
+///
+/// Percentiles are read off a histogram with logarithmically spaced buckets,
+/// and are rounded up to the upper bound of their bucket.
+pub fn quantization(inks: &Inks, lattice: Lattice, statistic: Statistic, count_threads: usize) -> f32 {
+    let count_buckets = ((DIFFERENCE_MAX / DIFFERENCE_MIN).ln() / RATIO_BUCKET.ln()).ceil() as usize + 1;
+    let bucket = |difference: f32| -> usize {
+        if difference <= DIFFERENCE_MIN {
+            0
+        }
+        else {
+            // Differences too large for the histogram land in its last bucket,
+            // which is open-ended.
+            (((difference / DIFFERENCE_MIN).ln() / RATIO_BUCKET.ln()).ceil() as usize).min(count_buckets - 1)
+        }
+    };
+
+    let mut histogram = vec![0_u64; count_buckets];
+    let mut maximum = 0.0_f32;
+
+    let steps = secondaries::steps(lattice.resolution, lattice.count_primaries, inks.limit);
+    let mapping = Mapping::Weighted(mapping::WEIGHTS_LAB);
+
+    // Compare each sample with the next sample along each ink, as long as that
+    // one respects the ink limit too.
+    secondaries::enumerate_with(inks, &mapping, lattice, count_threads, None, &mut |point, index| {
+        let digits = lattice.digits(index);
+        let spent = digits.iter().sum::<usize>();
+
+        if spent == steps {
+            return;
+        }
+
+        for index_primary in 0..lattice.count_primaries {
+            if digits[index_primary] + 1 == lattice.resolution {
+                continue;
+            }
+
+            let mut digits_next = digits.clone();
+            digits_next[index_primary] += 1;
+
+            let point_next = mapping.point(inks.mix(&lattice.fractions(lattice.index(&digits_next))), inks.white);
+            let difference = mapping.distance_squared(point, point_next).sqrt();
+
+            maximum = maximum.max(difference);
+            histogram[bucket(difference)] += 1;
+        }
+    });
+
+    match statistic {
+        Statistic::Maximum => maximum,
+        Statistic::Percentile95 => {
+            let count = histogram.iter().sum::<u64>();
+            let rank = (0.95 * count as f64).ceil() as u64;
+
+            let mut total = 0;
+            for (index_bucket, count_bucket) in histogram.into_iter().enumerate() {
+                total += count_bucket;
+
+                if total >= rank.max(1) {
+                    let bound = DIFFERENCE_MIN * RATIO_BUCKET.powi(index_bucket as i32);
+
+                    return if index_bucket == 0 { 0.0 } else { bound.min(maximum) };
+                }
+            }
+
+            0.0
+        },
+    }
+}
+
+/// Returns the smallest resolution found at which the given statistic of the
+/// quantization error of the given inks is within the given tolerance, along
+/// with the statistic at that resolution. Sampling is spread across the given
+/// number of threads.
+///
+/// If the tolerance can't be met without sampling more than `COUNT_MAX`
+/// secondary colors, returns the finest resolution measured along with its
+/// statistic as the error, or `None` if not even the coarsest resolution could
+/// be measured.
+///
+/// The quantization error shrinks about in proportion to the spacing of the
+/// samples, so each resolution measured predicts the next one to measure.
+pub fn resolution(inks: &Inks, tolerance: f32, statistic: Statistic, count_threads: usize) -> Result<(usize, f32), Option<(usize, f32)>> {
+    resolution_within(inks, tolerance, statistic, count_threads, COUNT_MAX)
+}
+
+/// Returns the resolution as `resolution` does, measuring no more than the
+/// given number of secondary colors.
+fn resolution_within(inks: &Inks, tolerance: f32, statistic: Statistic, count_threads: usize, count_max: u128) -> Result<(usize, f32), Option<(usize, f32)>> {
+    let count_primaries = inks.primaries.len();
+
+    let mut resolution = secondaries::resolution(TARGET_COARSE, count_primaries, inks.limit).ok_or(None)?;
+    let largest = largest(count_primaries, inks.limit, count_max).ok_or(None)?;
+    let mut finest = None;
+
+    resolution = resolution.min(largest);
+
+    loop {
+        let lattice = Lattice::new(resolution, count_primaries).ok_or(finest)?;
+        let error = quantization(inks, lattice, statistic, count_threads);
+
+        if error <= tolerance {
+            return Ok((resolution, error));
+        }
+
+        finest = Some((resolution, error));
+
+        if resolution == largest {
+            return Err(finest);
+        }
+
+        let prediction = ((resolution - 1) as f64 * (error / tolerance) as f64).ceil() + 1.0;
+
+        resolution = (prediction.min(largest as f64) as usize).max(resolution + 1);
+    }
+}
+
+/// Returns the largest resolution at which the given number of inks can be
+/// sampled under the given ink limit without exceeding the given number of
+/// secondary colors or `secondaries::STEPS_MAX`, or `None` if not even the
+/// coarsest resolution can.
+fn largest(count_primaries: usize, limit: f32, count_max: u128) -> Option<usize> {
+    let resolution_max = Lattice::resolution_max(count_primaries);
+
+    let fits = |resolution: usize| {
+        resolution <= resolution_max
+            && (count_primaries <= 1 || secondaries::steps(resolution, count_primaries, limit) <= secondaries::STEPS_MAX)
+            && secondaries::count(resolution, count_primaries, limit) <= count_max
+    };
+
+    let mut low = 2;
+    if !fits(low) {
+        return None;
+    }
+
+    let mut high = low;
+    while fits(high) {
+        if high == resolution_max {
+            return Some(high);
+        }
+
+        low = high;
+        high = high.saturating_mul(2).min(resolution_max);
+    }
+
+    // Both counts are nondecreasing with the resolution, so bisect the rest.
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+
+        if fits(middle) {
+            low = middle;
+        }
+        else {
+            high = middle;
+        }
+    }
+
+    Some(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    /// Tests that the quantization error shrinks with the spacing of the
+    /// samples, and that percentiles never exceed the maximum.
+    #[test]
+    fn test_quantization() {
+        let inks = testing::inks(2, 1.5);
+
+        let coarse = quantization(&inks, Lattice::new(11, 2).unwrap(), Statistic::Maximum, 2);
+        let fine = quantization(&inks, Lattice::new(21, 2).unwrap(), Statistic::Maximum, 2);
+        let percentile = quantization(&inks, Lattice::new(21, 2).unwrap(), Statistic::Percentile95, 2);
+
+        assert!(fine < coarse);
+        assert!((fine / coarse - 0.5).abs() <= 0.1);
+        assert!(0.0 < percentile && percentile <= fine);
+
+        assert_eq!(0.0, quantization(&testing::inks(2, 0.0), Lattice::new(11, 2).unwrap(), Statistic::Maximum, 2));
+    }
+
+    /// Tests that the resolution found meets the tolerance.
+    #[test]
+    fn test_resolution() {
+        let inks = testing::inks(2, f32::INFINITY);
+
+        for statistic in [Statistic::Maximum, Statistic::Percentile95] {
+            let (resolution, error) = resolution(&inks, 1.0, statistic, 2).unwrap();
+
+            assert!(error <= 1.0);
+            assert_eq!(error, quantization(&inks, Lattice::new(resolution, 2).unwrap(), statistic, 2));
+            assert!(quantization(&inks, Lattice::new(resolution / 2, 2).unwrap(), statistic, 2) > 1.0);
+        }
+
+        // A tolerance out of reach measures the largest resolution that fits
+        // before reporting how close the search got.
+        let count_max = 1 << 16;
+        let (resolution, error) = resolution_within(&inks, 1e-9, Statistic::Maximum, 2, count_max).unwrap_err().unwrap();
+
+        assert!(error > 1e-9);
+        assert!(secondaries::count(resolution, 2, inks.limit) <= count_max);
+        assert!(secondaries::count(resolution + 1, 2, inks.limit) > count_max);
+    }
+}
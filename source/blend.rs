@@ -0,0 +1,180 @@
+use crate::mapping::Mapping;
+use crate::vector::Vector3;
+
+/// The tolerance below which barycentric coordinates are still considered to
+/// lie inside their tetrahedron, absorbing rounding errors.
+const TOLERANCE_BARYCENTRIC: f32 = 1e-5;
+
+/// The largest number of neighbors that barycentric blending looks among for a
+/// tetrahedron, which it tries every one of, in the worst case, and there are
+/// already 1820 tetrahedra among 16 neighbors.
+pub const NEIGHBORS_BARYCENTRIC_MAX: usize = 16;
+
+/// A method of blending the ink fractions of the secondary colors nearest to a
+/// 3D LUT color into fractions for the 3D LUT color itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blend {
+    /// Each secondary color is weighted by the inverse of its squared distance
+    /// to the 3D LUT color.
+    InverseDistance,
+    /// The secondary colors are weighted by the barycentric coordinates of the
+    /// 3D LUT color in the tetrahedron of the nearest secondary colors that
+    /// encloses it, falling back on inverse distance weights if none does.
+    Barycentric,
+}
+
+/// Blends the ink fractions of the given neighbors, each given by its point in
+/// the mapping's space and its fractions, into fractions for the given target
+/// point, using the given blending method. The neighbors must be ordered
+/// nearest first.
+///
+/// Every blend is a convex combination, so blending fractions that lie between
+/// 0 and 1 and respect the ink limit produces fractions that do too.
+pub fn blend(blend: Blend, mapping: &Mapping, target: Vector3, neighbors: &[(Vector3, Vec<f32>)]) -> Vec<f32> {
+    let weights = match blend {
+        Blend::InverseDistance => None,
+        Blend::Barycentric => barycentric(target, neighbors),
+    }.unwrap_or_else(|| inverse_distance(mapping, target, neighbors));
+
+    let mut fractions = vec![0.0; neighbors[0].1.len()];
+
+    for ((_, fractions_neighbor), weight) in neighbors.iter().zip(weights) {
+        for (fraction, fraction_neighbor) in fractions.iter_mut().zip(fractions_neighbor) {
+            *fraction += weight * fraction_neighbor;
+        }
+    }
+
+    fractions
+}
+
+/// Returns the inverse distance weights of the given neighbors relative to the
+/// given target point. A neighbor right at the target gets all of the weight.
+fn inverse_distance(mapping: &Mapping, target: Vector3, neighbors: &[(Vector3, Vec<f32>)]) -> Vec<f32> {
+    let distances = neighbors.iter().map(|(point, _)| mapping.distance_squared(*point, target)).collect::<Vec<_>>();
+
+    if let Some(index) = distances.iter().position(|distance| *distance <= f32::MIN_POSITIVE) {
+        let mut weights = vec![0.0; neighbors.len()];
+        weights[index] = 1.0;
+
+        return weights;
+    }
+
+    let total = distances.iter().map(|distance| 1.0 / distance).sum::<f32>();
+
+    distances.into_iter().map(|distance| 1.0 / distance / total).collect()
+}
+
+/// Returns the barycentric weights of the given neighbors relative to the given
+/// target point, within the tetrahedron of neighbors enclosing the target that
+/// has the nearest neighbors, or `None` if no tetrahedron encloses the target.
+fn barycentric(target: Vector3, neighbors: &[(Vector3, Vec<f32>)]) -> Option<Vec<f32>> {
+    let count = neighbors.len();
+
+    // Tetrahedra are tried in lexicographic order of their neighbors, which
+    // tries the ones with the nearest neighbors first.
+    for a in 0..count {
+        for b in a + 1..count {
+            for c in b + 1..count {
+                for d in c + 1..count {
+                    let vertices = [a, b, c, d];
+
+                    let coordinates = match coordinates(target, vertices.map(|vertex| neighbors[vertex].0)) {
+                        Some(coordinates) => coordinates,
+                        None => continue,
+                    };
+
+                    if coordinates.iter().all(|coordinate| *coordinate >= -TOLERANCE_BARYCENTRIC) {
+                        let mut weights = vec![0.0; count];
+                        let total = coordinates.iter().map(|coordinate| coordinate.max(0.0)).sum::<f32>();
+
+                        for (vertex, coordinate) in vertices.into_iter().zip(coordinates) {
+                            weights[vertex] = coordinate.max(0.0) / total;
+                        }
+
+                        return Some(weights);
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns the barycentric coordinates of the given point relative to the given
+/// tetrahedron, or `None` if the tetrahedron is degenerate.
+fn coordinates(point: Vector3, vertices: [Vector3; 4]) -> Option<[f32; 4]> {
+    let edge_b = vertices[1] - vertices[0];
+    let edge_c = vertices[2] - vertices[0];
+    let edge_d = vertices[3] - vertices[0];
+    let offset = point - vertices[0];
+
+    let determinant = |a: Vector3, b: Vector3, c: Vector3| -> f32 {
+        a[0] * (b[1] * c[2] - b[2] * c[1])
+            - b[0] * (a[1] * c[2] - a[2] * c[1])
+            + c[0] * (a[1] * b[2] - a[2] * b[1])
+    };
+
+    let length = |edge: Vector3| -> f32 {
+        (edge[0].powi(2) + edge[1].powi(2) + edge[2].powi(2)).sqrt()
+    };
+
+    let volume = determinant(edge_b, edge_c, edge_d);
+
+    // Flat tetrahedra can't enclose anything.
+    let scale = length(edge_b) * length(edge_c) * length(edge_d);
+    if volume.abs() <= 1e-6 * scale || scale == 0.0 {
+        return None;
+    }
+
+    // Cramer's rule.
+    let coordinate_b = determinant(offset, edge_c, edge_d) / volume;
+    let coordinate_c = determinant(edge_b, offset, edge_d) / volume;
+    let coordinate_d = determinant(edge_b, edge_c, offset) / volume;
+
+    Some([1.0 - coordinate_b - coordinate_c - coordinate_d, coordinate_b, coordinate_c, coordinate_d])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbors() -> Vec<(Vector3, Vec<f32>)> {
+        vec![
+            (Vector3([0.0, 0.0, 0.0]), vec![0.0, 0.0]),
+            (Vector3([1.0, 0.0, 0.0]), vec![1.0, 0.0]),
+            (Vector3([0.0, 1.0, 0.0]), vec![0.0, 1.0]),
+            (Vector3([0.0, 0.0, 1.0]), vec![0.5, 0.5]),
+            (Vector3([2.0, 2.0, 2.0]), vec![1.0, 1.0]),
+        ]
+    }
+
+    /// Tests that barycentric blending reproduces fractions that vary linearly
+    /// across the enclosing tetrahedron.
+    #[test]
+    fn test_barycentric() {
+        let target = Vector3([0.25, 0.25, 0.25]);
+
+        let result = blend(Blend::Barycentric, &Mapping::Colorimetric, target, &neighbors());
+
+        assert!((result[0] - 0.375).abs() <= 1e-6);
+        assert!((result[1] - 0.375).abs() <= 1e-6);
+    }
+
+    /// Tests that inverse distance blending favors nearer neighbors, and that
+    /// barycentric blending falls back on it outside of every tetrahedron.
+    #[test]
+    fn test_inverse_distance() {
+        let target = Vector3([-1.0, 0.0, 0.0]);
+
+        let result = blend(Blend::InverseDistance, &Mapping::Colorimetric, target, &neighbors());
+        let fallback = blend(Blend::Barycentric, &Mapping::Colorimetric, target, &neighbors());
+
+        assert_eq!(result, fallback);
+        assert!(result[0] < 0.5 && result[1] < 0.5);
+
+        let exact = blend(Blend::InverseDistance, &Mapping::Colorimetric, Vector3([0.0, 1.0, 0.0]), &neighbors());
+
+        assert_eq!(vec![0.0, 1.0], exact);
+    }
+}
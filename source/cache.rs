@@ -0,0 +1,120 @@
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::inks::Inks;
+use crate::mapping::Mapping;
+use crate::vector::Vector3;
+
+/// The version of the way secondary colors are generated, which must be bumped
+/// whenever the same inputs would start producing different secondary colors,
+/// so that stale cache entries are never picked up.
+const VERSION: u32 = 1;
+
+/// Returns the path, in the given cache directory, of the secondary colors
+/// generated from the given inks under the given mapping at the given
+/// resolution, refined around the given points if adaptive sampling is used.
+///
+/// The path is named after a hash of all of those inputs, so it changes
+/// whenever any of them do.
+pub fn path(directory: &Path, inks: &Inks, mapping: &Mapping, resolution: usize, points: Option<&[Vector3]>) -> PathBuf {
+    /// Feeds the given vector to the given hasher.
+    fn write_vector(hasher: &mut Fnv, vector: Vector3) {
+        for axis in 0..3 {
+            hasher.write_u32(vector[axis].to_bits());
+        }
+    }
+
+    let mut hasher = Fnv::new();
+
+    hasher.write_u32(VERSION);
+
+    write_vector(&mut hasher, inks.white);
+    hasher.write_u64(inks.primaries.len() as u64);
+    for primary in inks.primaries.iter() {
+        write_vector(&mut hasher, *primary);
+    }
+    hasher.write_u32(inks.limit.to_bits());
+
+    match mapping {
+        Mapping::Colorimetric => hasher.write_u8(0),
+        Mapping::Weighted(weights) => {
+            hasher.write_u8(1);
+            write_vector(&mut hasher, *weights);
+        },
+    }
+
+    hasher.write_u64(resolution as u64);
+
+    match points {
+        None => hasher.write_u8(0),
+        Some(points) => {
+            hasher.write_u8(1);
+            hasher.write_u64(points.len() as u64);
+            for point in points {
+                write_vector(&mut hasher, *point);
+            }
+        },
+    }
+
+    directory.join(format!("{:016x}.bin", hasher.finish()))
+}
+
+/// The 64-bit FNV-1a hash function. Unlike the standard library's hasher, its
+/// output is guaranteed to stay the same between builds of the program.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    /// Tests that the hash matches a published FNV-1a test vector.
+    #[test]
+    fn test_fnv() {
+        let mut hasher = Fnv::new();
+        hasher.write(b"foobar");
+
+        assert_eq!(0x85944171f73967e8, hasher.finish());
+    }
+
+    /// Tests that changing any input changes the path.
+    #[test]
+    fn test_path() {
+        let directory = Path::new("cache");
+        let inks = testing::inks(1, 1.0);
+        let inks_limited = Inks {
+            limit: 0.5,
+            ..inks.clone()
+        };
+        let points = [Vector3([0.5, 0.5, 0.5])];
+
+        let reference = path(directory, &inks, &Mapping::Colorimetric, 10, None);
+
+        assert_eq!(reference, path(directory, &inks, &Mapping::Colorimetric, 10, None));
+        assert_ne!(reference, path(directory, &inks_limited, &Mapping::Colorimetric, 10, None));
+        assert_ne!(reference, path(directory, &inks, &Mapping::Weighted(Vector3([1.0, 1.0, 1.0])), 10, None));
+        assert_ne!(reference, path(directory, &inks, &Mapping::Colorimetric, 11, None));
+        assert_ne!(reference, path(directory, &inks, &Mapping::Colorimetric, 10, Some(&points)));
+    }
+}
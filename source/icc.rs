@@ -0,0 +1,574 @@
+use foreign_types::ForeignType;
+use lcms2::CIELab;
+use lcms2::CIELabExt;
+use lcms2::CIEXYZ;
+use lcms2::CIEXYZExt;
+use lcms2::CIExyY;
+use lcms2::ColorSpaceSignature;
+use lcms2::Flags;
+use lcms2::GlobalContext;
+use lcms2::Intent;
+use lcms2::Locale;
+use lcms2::MLU;
+use lcms2::NamedColorList;
+use lcms2::Pipeline;
+use lcms2::PixelFormat;
+use lcms2::Profile;
+use lcms2::ProfileClassSignature;
+use lcms2::Stage;
+use lcms2::Tag;
+use lcms2::TagSignature;
+use lcms2::ToneCurve;
+use lcms2::Transform;
+use std::ffi::CString;
+use std::io;
+use std::io::Write;
+use std::ptr;
+
+use crate::inks::Inks;
+use crate::lut::LutWriter;
+use crate::lut::Metadata;
+use crate::lut::SeparationWriter;
+use crate::optimize::Interpolation;
+use crate::vector::Vector3;
+
+/// The version of the ICC specification that profiles are written with.
+const VERSION: f64 = 4.3;
+
+/// The D50 white point of the PCS, in XYZ space.
+const WHITE_D50_XYZ: CIEXYZ = CIEXYZ {
+    X: 0.9642,
+    Y: 1.0,
+    Z: 0.8249,
+};
+
+/// The D50 white point of the PCS, in xyY space.
+const WHITE_D50: CIExyY = CIExyY {
+    x: WHITE_D50_XYZ.X / (WHITE_D50_XYZ.X + WHITE_D50_XYZ.Y + WHITE_D50_XYZ.Z),
+    y: WHITE_D50_XYZ.Y / (WHITE_D50_XYZ.X + WHITE_D50_XYZ.Y + WHITE_D50_XYZ.Z),
+    Y: 1.0,
+};
+
+/// The most nodes that the CLUT from the inks of an output profile to the PCS
+/// has, as many as a 33-point 3D LUT.
+const COUNT_NODES_INKS: usize = 33 * 33 * 33;
+
+/// The most primary colors that output profiles can have inks for, as their
+/// device spaces go from 2CLR to 15CLR.
+pub const COUNT_INKS_MAX: usize = 15;
+
+/// The CIE76 color difference beyond which colors that the inks mix from their
+/// separation are out of the gamut of the inks, as Little CMS takes it.
+const TOLERANCE_GAMUT: f64 = 5.0;
+
+/// A writer of 3D LUTs as ICC profiles: RGB→RGB device links that apply the
+/// 3D LUT to colors in the profile's space, or abstract Lab→Lab profiles that
+/// apply it to colors in the PCS, moving them into the profile's space and
+/// back.
+pub struct Icc<W: Write> {
+    output: W,
+    metadata: Metadata,
+    abstract_lab: bool,
+    size: usize,
+    colors: Vec<Vector3<f64>>,
+}
+
+impl<W: Write> Icc<W> {
+    /// Returns a writer to the given output, describing its profiles with the
+    /// given metadata, whose profile an abstract profile is written for if
+    /// asked for, rather than a device link.
+    pub fn new(output: W, metadata: Metadata, abstract_lab: bool) -> Self {
+        Self {
+            output,
+            metadata,
+            abstract_lab,
+            size: 0,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Icc<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        self.colors = Vec::with_capacity(size.pow(3));
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.colors.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let link = device_link(self.size, &self.colors).map_err(io::Error::other)?;
+
+        let mut profile = if self.abstract_lab {
+            let profile = Profile::new_icc(&self.metadata.profile).map_err(io::Error::other)?;
+
+            abstract_profile(&profile, &link).map_err(io::Error::other)?
+        }
+        else {
+            link
+        };
+
+        describe(&mut profile, &self.metadata);
+        self.output.write_all(&profile.icc().map_err(io::Error::other)?)?;
+
+        self.colors = Vec::new();
+
+        self.output.flush()
+    }
+}
+
+/// A writer of the masks of a color separation as an ICC output profile whose
+/// device space is the inks of the primary colors: its AToB tags mix the inks
+/// as the color separation does, and its BToA tags separate colors as the masks
+/// do.
+///
+/// Of the 3D LUTs of the color separation, only the masks are kept. The
+/// profile is written once they're finished.
+pub struct Nclr<W: Write> {
+    output: W,
+    metadata: Metadata,
+    primaries: Vec<Vector3<f64>>,
+    size: usize,
+    masks: Vec<Vec<f64>>,
+}
+
+impl<W: Write> Nclr<W> {
+    /// Returns a writer to the given output of an output profile, recording
+    /// the given metadata, for inks of the given primary colors, in the
+    /// profile's space and between 0 and 1.
+    pub fn new(output: W, metadata: Metadata, primaries: Vec<Vector3<f64>>) -> Self {
+        Self {
+            output,
+            metadata,
+            masks: vec![Vec::new(); primaries.len()],
+            primaries,
+            size: 0,
+        }
+    }
+}
+
+impl<W: Write> SeparationWriter for Nclr<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        for mask in self.masks.iter_mut() {
+            *mask = Vec::with_capacity(size.pow(3));
+        }
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, index: usize, colors: &[Vector3<f64>]) -> io::Result<()> {
+        // Masks are grays, so any component of theirs is the fraction.
+        match index {
+            0 => {},
+            index if index % 2 == 0 => self.masks[index / 2 - 1].extend(colors.iter().map(|color| color[0])),
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let profile = Profile::new_icc(&self.metadata.profile).map_err(io::Error::other)?;
+        let mut profile = output_profile(&profile, &self.primaries, self.size, &self.masks)?;
+
+        describe(&mut profile, &self.metadata);
+        self.output.write_all(&profile.icc().map_err(io::Error::other)?)?;
+
+        for mask in self.masks.iter_mut() {
+            *mask = Vec::new();
+        }
+
+        self.output.flush()
+    }
+}
+
+/// Writes the name and description of the given metadata to the given
+/// profile.
+fn describe(profile: &mut Profile, metadata: &Metadata) {
+    let mut description = MLU::new(1);
+    description.set_text(&metadata.name, Locale::none());
+    profile.write_tag(TagSignature::ProfileDescriptionTag, Tag::MLU(&description));
+
+    let mut model = MLU::new(1);
+    model.set_text(&metadata.description, Locale::none());
+    profile.write_tag(TagSignature::DeviceModelDescTag, Tag::MLU(&model));
+}
+
+/// Returns a pipeline of a CLUT of the given number of input and output
+/// channels, grid points and table, first input channel slowest.
+fn pipeline(count_inputs: usize, count_outputs: usize, grid: usize, table: &[u16]) -> lcms2::LCMSResult<Pipeline> {
+    // The CLUT is framed by identity curves, which every LUT tag type needs.
+    let curve = ToneCurve::new(1.0);
+    let pipeline = Pipeline::new(count_inputs, count_outputs)?;
+    for stage in [
+        Stage::new_tone_curves(&vec![&*curve; count_inputs])?,
+        Stage::new_clut(grid, count_inputs as u32, count_outputs as u32, Some(table))?,
+        Stage::new_tone_curves(&vec![&*curve; count_outputs])?,
+    ] {
+        // The lcms2 crate has no way to add stages to pipelines.
+        let inserted = unsafe {
+            lcms2_sys::cmsPipelineInsertStage(pipeline.as_ptr(), lcms2_sys::StageLoc::AT_END, stage.into_ptr())
+        };
+        if !inserted {
+            return Err(lcms2::Error::ObjectCreationError);
+        }
+    }
+
+    Ok(pipeline)
+}
+
+/// Returns an RGB→RGB device link that applies the 3D LUT of the given size
+/// with the given colors, red fastest.
+pub fn device_link(size: usize, colors: &[Vector3<f64>]) -> lcms2::LCMSResult<Profile> {
+    // The first input channel of a CLUT is its slowest.
+    let mut table = Vec::with_capacity(3 * size.pow(3));
+    for index_red in 0..size {
+        for index_green in 0..size {
+            for index_blue in 0..size {
+                let color = colors[(index_blue * size + index_green) * size + index_red];
+
+                table.extend(color.0.map(|component| (component.clamp(0.0, 1.0) * 65535.0).round() as u16));
+            }
+        }
+    }
+
+    let pipeline = pipeline(3, 3, size, &table)?;
+
+    let mut profile = Profile::new_placeholder();
+    profile.set_version(VERSION);
+    profile.set_device_class(ProfileClassSignature::LinkClass);
+    profile.set_color_space(ColorSpaceSignature::RgbData);
+    profile.set_pcs(ColorSpaceSignature::RgbData);
+    profile.write_tag(TagSignature::AToB0Tag, Tag::Pipeline(&pipeline));
+
+    // Rebuilding the device link from a transform through it adds the tags
+    // that device links must have.
+    let transform: Transform<[u16; 3], [u16; 3]> = Transform::new_multiprofile(
+        &[&profile],
+        PixelFormat::RGB_16,
+        PixelFormat::RGB_16,
+        Intent::Perceptual,
+        Flags::NO_OPTIMIZE | Flags::KEEP_SEQUENCE
+    )?;
+
+    Profile::new_device_link(&transform, VERSION, Flags::KEEP_SEQUENCE)
+}
+
+/// Returns an abstract Lab→Lab profile that moves colors from the PCS into the
+/// space of the given RGB profile, applies the given device link, and moves
+/// them back.
+pub fn abstract_profile(profile: &Profile, link: &Profile) -> lcms2::LCMSResult<Profile> {
+    let profile_lab = Profile::new_lab4_context(GlobalContext::new(), &WHITE_D50)?;
+
+    let transform: Transform<[f64; 3], [f64; 3]> = Transform::new_multiprofile(
+        &[&profile_lab, profile, link, profile, &profile_lab],
+        PixelFormat::Lab_DBL,
+        PixelFormat::Lab_DBL,
+        Intent::RelativeColorimetric,
+        Flags::default()
+    )?;
+
+    Profile::new_device_link(&transform, VERSION, Flags::GUESS_DEVICE_CLASS)
+}
+
+/// Returns an output profile whose device space is the inks of the given primary
+/// colors, in the space of the given RGB profile and between 0 and 1, whose
+/// AToB tags mix the inks as the color separation does, and whose BToA tags
+/// separate colors with the given masks of the given size, red fastest. There
+/// must be between 2 and `COUNT_INKS_MAX` primary colors.
+pub fn output_profile(profile: &Profile, primaries: &[Vector3<f64>], size: usize, masks: &[Vec<f64>]) -> io::Result<Profile> {
+    let count = primaries.len();
+
+    let signature = match count {
+        2 => ColorSpaceSignature::Sig2colorData,
+        3 => ColorSpaceSignature::Sig3colorData,
+        4 => ColorSpaceSignature::Sig4colorData,
+        5 => ColorSpaceSignature::Sig5colorData,
+        6 => ColorSpaceSignature::Sig6colorData,
+        7 => ColorSpaceSignature::Sig7colorData,
+        8 => ColorSpaceSignature::Sig8colorData,
+        9 => ColorSpaceSignature::Sig9colorData,
+        10 => ColorSpaceSignature::Sig10colorData,
+        11 => ColorSpaceSignature::Sig11colorData,
+        12 => ColorSpaceSignature::Sig12colorData,
+        13 => ColorSpaceSignature::Sig13colorData,
+        14 => ColorSpaceSignature::Sig14colorData,
+        15 => ColorSpaceSignature::Sig15colorData,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("output profiles need between 2 and {} inks, not {}", COUNT_INKS_MAX, count))),
+    };
+
+    let profile_lab = Profile::new_lab4_context(GlobalContext::new(), &WHITE_D50).map_err(io::Error::other)?;
+
+    // Move the paper and inks into the PCS, as the color separation does.
+    let transform: Transform<Vector3<f64>, Vector3<f64>> = Transform::new(
+        profile,
+        PixelFormat::RGB_DBL,
+        &Profile::new_xyz(),
+        PixelFormat::XYZ_DBL,
+        Intent::AbsoluteColorimetric
+    ).map_err(io::Error::other)?;
+
+    let mut colors = vec![Vector3([1.0, 1.0, 1.0])];
+    colors.extend_from_slice(primaries);
+    transform.transform_in_place(&mut colors);
+
+    let inks = Inks {
+        white: colors[0],
+        primaries: colors[1..].to_vec(),
+        limit: count as f32,
+    };
+
+    // Returns the color, in Lab space, of the given color, in XYZ space,
+    // relative to the paper.
+    let lab = |color: Vector3<f64>| -> CIELab {
+        let color = color / inks.white;
+
+        CIEXYZ {
+            X: color[0] * WHITE_D50_XYZ.X,
+            Y: color[1] * WHITE_D50_XYZ.Y,
+            Z: color[2] * WHITE_D50_XYZ.Z,
+        }.to_lab(&WHITE_D50_XYZ)
+    };
+
+    // Mix the inks at every node of a CLUT as fine as the number of inks
+    // allows, first ink slowest.
+    let grid = (2..=33).rev().find(|grid: &usize| grid.checked_pow(count as u32).is_some_and(|count_nodes| count_nodes <= COUNT_NODES_INKS)).unwrap_or(2);
+
+    let mut table_inks = Vec::with_capacity(3 * grid.pow(count as u32));
+    let mut fractions = vec![0.0; count];
+    for index in 0..grid.pow(count as u32) {
+        for (ink, fraction) in fractions.iter_mut().enumerate() {
+            *fraction = (index / grid.pow((count - 1 - ink) as u32) % grid) as f32 / (grid - 1) as f32;
+        }
+
+        table_inks.extend(lab(inks.mix(&fractions)).encoded());
+    }
+
+    // Separate the colors at every node of a CLUT of the masks' size, L
+    // slowest, by moving them into the profile's space and interpolating the
+    // masks there.
+    let mut colors_lab = Vec::with_capacity(size.pow(3));
+    for index_lightness in 0..size {
+        for index_a in 0..size {
+            for index_b in 0..size {
+                let color = CIELab::from_encoded(&[index_lightness, index_a, index_b].map(|index|
+                    (index as f64 * 65535.0 / (size - 1) as f64).round() as u16
+                ));
+
+                colors_lab.push([color.L, color.a, color.b]);
+            }
+        }
+    }
+
+    let transform: Transform<[f64; 3], [f64; 3]> = Transform::new(
+        &profile_lab,
+        PixelFormat::Lab_DBL,
+        profile,
+        PixelFormat::RGB_DBL,
+        Intent::RelativeColorimetric
+    ).map_err(io::Error::other)?;
+
+    let mut colors_rgb = vec![[0.0; 3]; colors_lab.len()];
+    transform.transform_pixels(&colors_lab, &mut colors_rgb);
+
+    let mut table_separation = Vec::with_capacity(count * size.pow(3));
+    let mut table_gamut = Vec::with_capacity(size.pow(3));
+    for (color_lab, color_rgb) in colors_lab.iter().zip(colors_rgb) {
+        let point = color_rgb.map(|component| (component.clamp(0.0, 1.0) * (size - 1) as f64) as f32);
+
+        for (fraction, mask) in fractions.iter_mut().zip(masks) {
+            *fraction = Interpolation::Tetrahedral.interpolate(size, mask, point).clamp(0.0, 1.0) as f32;
+        }
+
+        table_separation.extend(fractions.iter().map(|fraction| (fraction * 65535.0).round() as u16));
+
+        // Colors are out of the inks' gamut if the inks they're separated into
+        // mix too far from them.
+        let color_mix = lab(inks.mix(&fractions));
+        let difference = ((color_mix.L - color_lab[0]).powi(2) + (color_mix.a - color_lab[1]).powi(2) + (color_mix.b - color_lab[2]).powi(2)).sqrt();
+
+        table_gamut.push(if difference <= TOLERANCE_GAMUT { 0 } else { 65535 });
+    }
+
+    // Colorant tables give the colors of the inks in the legacy encoding of
+    // Lab, which the lcms2 crate has no way to add to.
+    let colorants = NamedColorList::new(count, 0, "", "").map_err(io::Error::other)?;
+    for (index, primary) in inks.primaries.iter().enumerate() {
+        let name = CString::new(format!("Primary color {}", index + 1)).unwrap();
+        let mut color = lab(*primary).encoded_v2();
+
+        let appended = unsafe {
+            lcms2_sys::cmsAppendNamedColor(colorants.as_ptr(), name.as_ptr(), color.as_mut_ptr(), ptr::null_mut())
+        };
+        if appended == 0 {
+            return Err(io::Error::other(lcms2::Error::ObjectCreationError));
+        }
+    }
+
+    let mut profile_output = Profile::new_placeholder();
+    profile_output.set_version(VERSION);
+    profile_output.set_device_class(ProfileClassSignature::OutputClass);
+    profile_output.set_color_space(signature);
+    profile_output.set_pcs(ColorSpaceSignature::LabData);
+
+    // Every rendering intent is colorimetric, as there's nothing to tell them
+    // apart by.
+    let pipeline_inks = pipeline(count, 3, grid, &table_inks).map_err(io::Error::other)?;
+    let pipeline_separation = pipeline(3, count, size, &table_separation).map_err(io::Error::other)?;
+    let pipeline_gamut = pipeline(3, 1, size, &table_gamut).map_err(io::Error::other)?;
+
+    profile_output.write_tag(TagSignature::AToB0Tag, Tag::Pipeline(&pipeline_inks));
+    profile_output.link_tag(TagSignature::AToB1Tag, TagSignature::AToB0Tag);
+    profile_output.link_tag(TagSignature::AToB2Tag, TagSignature::AToB0Tag);
+    profile_output.write_tag(TagSignature::BToA0Tag, Tag::Pipeline(&pipeline_separation));
+    profile_output.link_tag(TagSignature::BToA1Tag, TagSignature::BToA0Tag);
+    profile_output.link_tag(TagSignature::BToA2Tag, TagSignature::BToA0Tag);
+    profile_output.write_tag(TagSignature::GamutTag, Tag::Pipeline(&pipeline_gamut));
+
+    profile_output.write_tag(TagSignature::MediaWhitePointTag, Tag::CIEXYZ(&CIEXYZ {
+        X: inks.white[0],
+        Y: inks.white[1],
+        Z: inks.white[2],
+    }));
+    profile_output.write_tag(TagSignature::ColorantTableTag, Tag::NAMEDCOLORLIST(&colorants));
+
+    let mut copyright = MLU::new(1);
+    copyright.set_text("No copyright, use freely", Locale::none());
+    profile_output.write_tag(TagSignature::CopyrightTag, Tag::MLU(&copyright));
+
+    Ok(profile_output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the colors of a 3D LUT of the given size that inverts colors.
+    fn colors_inverted(size: usize) -> Vec<Vector3<f64>> {
+        (0..size.pow(3)).map(|index| {
+            let coordinates = [index % size, index / size % size, index / (size * size)];
+
+            Vector3(coordinates.map(|coordinate| 1.0 - coordinate as f64 / (size - 1) as f64))
+        }).collect()
+    }
+
+    /// Tests that device links apply the 3D LUT at its nodes and in between.
+    #[test]
+    fn test_device_link() {
+        let link = Profile::new_icc(&device_link(3, &colors_inverted(3)).unwrap().icc().unwrap()).unwrap();
+
+        assert_eq!(ProfileClassSignature::LinkClass, link.device_class());
+        assert_eq!(ColorSpaceSignature::RgbData, link.color_space());
+        assert_eq!(ColorSpaceSignature::RgbData, link.pcs());
+
+        let transform: Transform<[u16; 3], [u16; 3]> = Transform::new_multiprofile(
+            &[&link],
+            PixelFormat::RGB_16,
+            PixelFormat::RGB_16,
+            Intent::Perceptual,
+            Flags::default()
+        ).unwrap();
+
+        let colors = [[0, 0, 0], [65535, 32768, 0], [1000, 20000, 40000]];
+        let mut colors_link = [[0; 3]; 3];
+        transform.transform_pixels(&colors, &mut colors_link);
+
+        for (color, color_link) in colors.iter().zip(colors_link) {
+            for channel in 0..3 {
+                assert!((65535 - color[channel] as i32 - color_link[channel] as i32).abs() <= 2);
+            }
+        }
+    }
+
+    /// Tests that abstract profiles apply the 3D LUT in the profile's space.
+    #[test]
+    fn test_abstract_profile() {
+        let profile = Profile::new_srgb();
+        let link = device_link(5, &colors_inverted(5)).unwrap();
+        let profile_abstract = Profile::new_icc(&abstract_profile(&profile, &link).unwrap().icc().unwrap()).unwrap();
+
+        assert_eq!(ProfileClassSignature::AbstractClass, profile_abstract.device_class());
+        assert_eq!(ColorSpaceSignature::LabData, profile_abstract.color_space());
+        assert_eq!(ColorSpaceSignature::LabData, profile_abstract.pcs());
+
+        // White through the abstract profile comes out as black, and black as
+        // white.
+        let profile_lab = Profile::new_lab4_context(GlobalContext::new(), &WHITE_D50).unwrap();
+        let transform: Transform<[f64; 3], [f64; 3]> = Transform::new_multiprofile(
+            &[&profile_lab, &profile_abstract, &profile_lab],
+            PixelFormat::Lab_DBL,
+            PixelFormat::Lab_DBL,
+            Intent::RelativeColorimetric,
+            Flags::default()
+        ).unwrap();
+
+        let mut colors = [[100.0, 0.0, 0.0], [0.0, 0.0, 0.0]];
+        transform.transform_in_place(&mut colors);
+
+        assert!(colors[0][0] <= 1.0, "{:?}", colors);
+        assert!(colors[1][0] >= 99.0, "{:?}", colors);
+        for color in colors {
+            assert!(color[1].abs() <= 1.0 && color[2].abs() <= 1.0, "{:?}", colors);
+        }
+    }
+
+    /// Tests that output profiles mix the inks, and separate colors as the
+    /// masks do.
+    #[test]
+    fn test_output_profile() {
+        // Cyan ink, which takes away linear red just as it's mixed, and black
+        // ink that the masks never use.
+        let primaries = [Vector3([0.0, 1.0, 1.0]), Vector3([0.0, 0.0, 0.0])];
+        let masks = [
+            colors_inverted(17).iter().map(|color| {
+                let red = 1.0 - color[0];
+
+                1.0 - if red <= 0.04045 { red / 12.92 } else { ((red + 0.055) / 1.055).powf(2.4) }
+            }).collect(),
+            vec![0.0; 17 * 17 * 17],
+        ];
+
+        let profile = Profile::new_srgb();
+        let profile_output = Profile::new_icc(&output_profile(&profile, &primaries, 17, &masks).unwrap().icc().unwrap()).unwrap();
+
+        // A single ink makes no output profile.
+        assert_eq!(io::ErrorKind::InvalidInput, output_profile(&profile, &primaries[..1], 17, &masks[..1]).unwrap_err().kind());
+
+        assert_eq!(ProfileClassSignature::OutputClass, profile_output.device_class());
+        assert_eq!(ColorSpaceSignature::Sig2colorData, profile_output.color_space());
+        assert_eq!(ColorSpaceSignature::LabData, profile_output.pcs());
+        for tag in [TagSignature::BToA2Tag, TagSignature::GamutTag, TagSignature::ColorantTableTag, TagSignature::MediaWhitePointTag] {
+            assert!(profile_output.has_tag(tag), "{:?}", tag);
+        }
+
+        // The inks are 16-bit integers, with 2 channels in no particular
+        // color space, which lcms2 has no constant for.
+        let format_inks = PixelFormat(2 << 3 | 2);
+        let profile_lab = Profile::new_lab4_context(GlobalContext::new(), &WHITE_D50).unwrap();
+        let transform_inks: Transform<[u16; 2], [f64; 3]> = Transform::new(&profile_output, format_inks, &profile_lab, PixelFormat::Lab_DBL, Intent::RelativeColorimetric).unwrap();
+        let transform_separation: Transform<[f64; 3], [u16; 2]> = Transform::new(&profile_lab, PixelFormat::Lab_DBL, &profile_output, format_inks, Intent::RelativeColorimetric).unwrap();
+
+        // Bare paper is white, and full cyan is sRGB's cyan.
+        let mut colors = [[0.0; 3]; 3];
+        transform_inks.transform_pixels(&[[0, 0], [65535, 0], [32768, 0]], &mut colors);
+
+        assert!(colors[0][0] >= 99.0 && colors[0][1].abs() <= 1.0 && colors[0][2].abs() <= 1.0, "{:?}", colors);
+        assert!((colors[1][0] - 91.1).abs() <= 1.0 && colors[1][1] <= -40.0, "{:?}", colors);
+
+        // White takes no ink, and cyan takes as much cyan ink as it was mixed
+        // from, if not exactly so at the edge of the gamut.
+        let mut inks = [[0; 2]; 3];
+        transform_separation.transform_pixels(&colors, &mut inks);
+
+        assert!(inks[0][0] <= 1000 && inks[0][1] <= 1000, "{:?}", inks);
+        assert!(inks[1][0] >= 32768 && inks[1][1] <= 1000, "{:?}", inks);
+        assert!((inks[2][0] as i32 - 32768).abs() <= 3000 && inks[2][1] <= 1000, "{:?}", inks);
+    }
+}
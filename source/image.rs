@@ -0,0 +1,262 @@
+use png::BitDepth;
+use png::ColorType;
+use png::Encoder;
+use std::io;
+use std::io::Write;
+
+use crate::lut::LutWriter;
+use crate::vector::Vector3;
+
+/// The bit depths that the colors of images can have.
+pub const DEPTHS: [u32; 2] = [8, 16];
+
+/// Returns the level of the Hald CLUT image of a 3D LUT of the given size, if
+/// the size is the square of an integer greater than 1.
+pub fn level(size: usize) -> Option<usize> {
+    let level = (size as f64).sqrt().round() as usize;
+
+    (level >= 2 && level * level == size).then_some(level)
+}
+
+/// A writer of 3D LUTs as Hald CLUT images: square PNG images of the given
+/// level, whose pixels are the colors of a 3D LUT of the square of that size,
+/// red fastest, row by row.
+pub struct Hald<W: Write> {
+    output: W,
+    depth: u32,
+    size: usize,
+    colors: Vec<Vector3<f64>>,
+}
+
+impl<W: Write> Hald<W> {
+    /// Returns a writer to the given output, whose colors are written with the
+    /// given bit depth.
+    pub fn new(output: W, depth: u32) -> Self {
+        Self {
+            output,
+            depth,
+            size: 0,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Hald<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        if level(size).is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("a 3D LUT of size {} has no Hald CLUT image", size)));
+        }
+
+        self.size = size;
+        self.colors = Vec::with_capacity(size.pow(3));
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.colors.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let width = level(self.size).unwrap().pow(3);
+
+        write_png(&mut self.output, width, width, self.depth, &self.colors)?;
+
+        self.colors = Vec::new();
+
+        self.output.flush()
+    }
+}
+
+/// How the slices of a 3D LUT of constant blue are laid out in a strip image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Side by side, making an image as wide as the 3D LUT's size squared and
+    /// as tall as its size (e.g. 1024×32).
+    Horizontal,
+    /// Atop one another, making an image as wide as the 3D LUT's size and as
+    /// tall as its size squared (e.g. 32×1024).
+    Vertical,
+}
+
+/// A writer of 3D LUTs as strip images: PNG images made of the slices of the
+/// 3D LUT of constant blue, in order of increasing blue and laid out as given,
+/// whose pixels go red fastest from left to right and green from top to
+/// bottom, as game engines take 2D LUT textures. If flipped, the images are
+/// upside down, for engines whose textures start at the bottom.
+pub struct Strip<W: Write> {
+    output: W,
+    layout: Layout,
+    flip: bool,
+    depth: u32,
+    size: usize,
+    colors: Vec<Vector3<f64>>,
+}
+
+impl<W: Write> Strip<W> {
+    /// Returns a writer to the given output, whose images have the given
+    /// layout and are flipped if asked for, and whose colors are written with
+    /// the given bit depth.
+    pub fn new(output: W, layout: Layout, flip: bool, depth: u32) -> Self {
+        Self {
+            output,
+            layout,
+            flip,
+            depth,
+            size: 0,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Strip<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        self.colors = Vec::with_capacity(size.pow(3));
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.colors.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let size = self.size;
+        let (width, height) = match self.layout {
+            Layout::Horizontal => (size * size, size),
+            Layout::Vertical => (size, size * size),
+        };
+
+        let mut pixels = vec![Vector3([0.0; 3]); width * height];
+        for (index, color) in self.colors.iter().enumerate() {
+            let (index_red, index_green, index_blue) = (index % size, index / size % size, index / size / size);
+
+            let (x, y) = match self.layout {
+                Layout::Horizontal => (index_blue * size + index_red, index_green),
+                Layout::Vertical => (index_red, index_blue * size + index_green),
+            };
+            let y = if self.flip { height - 1 - y } else { y };
+
+            pixels[y * width + x] = *color;
+        }
+
+        write_png(&mut self.output, width, height, self.depth, &pixels)?;
+
+        self.colors = Vec::new();
+
+        self.output.flush()
+    }
+}
+
+/// Writes the given colors to the given output as a PNG image of the given
+/// dimensions and bit depth, row by row.
+pub fn write_png(output: &mut impl Write, width: usize, height: usize, depth: u32, colors: &[Vector3<f64>]) -> io::Result<()> {
+    let maximum = ((1_u32 << depth) - 1) as f64;
+
+    let mut data = Vec::with_capacity(colors.len() * 3 * depth as usize / 8);
+    for color in colors {
+        for component in color.0 {
+            let value = (component.clamp(0.0, 1.0) * maximum).round() as u16;
+
+            match depth {
+                8 => data.push(value as u8),
+                _ => data.extend_from_slice(&value.to_be_bytes()),
+            }
+        }
+    }
+
+    let mut encoder = Encoder::new(output, width as u32, height as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(if depth == 8 { BitDepth::Eight } else { BitDepth::Sixteen });
+
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use png::Decoder;
+
+    /// Tests the levels of Hald CLUT images.
+    #[test]
+    fn test_level() {
+        assert_eq!(Some(2), level(4));
+        assert_eq!(Some(8), level(64));
+        assert_eq!(None, level(1));
+        assert_eq!(None, level(33));
+    }
+
+    /// Tests that Hald CLUT images hold the colors of the 3D LUT in order, at
+    /// the bit depth asked for.
+    #[test]
+    fn test_hald() {
+        let colors = (0..64).map(|index| Vector3([index as f64 / 63.0, 0.5, 1.5])).collect::<Vec<_>>();
+
+        for depth in DEPTHS {
+            let mut output = Vec::new();
+
+            let mut writer = Hald::new(&mut output, depth);
+            writer.begin(4).unwrap();
+            writer.write_colors(&colors[..16]).unwrap();
+            writer.write_colors(&colors[16..]).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = Decoder::new(output.as_slice()).read_info().unwrap();
+            let mut data = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut data).unwrap();
+
+            assert_eq!((8, 8), (info.width, info.height));
+            assert_eq!(depth, info.bit_depth as u32);
+
+            let maximum = (1_u32 << depth) - 1;
+            let value = |index: usize| -> u32 {
+                match depth {
+                    8 => data[index] as u32,
+                    _ => u16::from_be_bytes([data[2 * index], data[2 * index + 1]]) as u32,
+                }
+            };
+
+            assert_eq!([0, maximum.div_ceil(2), maximum], [value(0), value(1), value(2)]);
+            assert_eq!(maximum, value(3 * 63));
+        }
+
+        let mut writer = Hald::new(Vec::new(), 8);
+        assert!(writer.begin(5).is_err());
+    }
+
+    /// Tests that strip images lay out the slices of the 3D LUT as asked for.
+    #[test]
+    fn test_strip() {
+        // Every color is its own index within the 3D LUT, in its red.
+        let colors = (0..8).map(|index| Vector3([index as f64 / 255.0, 0.0, 0.0])).collect::<Vec<_>>();
+
+        for (layout, flip, dimensions, reds) in [
+            (Layout::Horizontal, false, (4, 2), [0, 1, 4, 5, 2, 3, 6, 7]),
+            (Layout::Horizontal, true, (4, 2), [2, 3, 6, 7, 0, 1, 4, 5]),
+            (Layout::Vertical, false, (2, 4), [0, 1, 2, 3, 4, 5, 6, 7]),
+            (Layout::Vertical, true, (2, 4), [6, 7, 4, 5, 2, 3, 0, 1]),
+        ] {
+            let mut output = Vec::new();
+
+            let mut writer = Strip::new(&mut output, layout, flip, 8);
+            writer.begin(2).unwrap();
+            writer.write_colors(&colors).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = Decoder::new(output.as_slice()).read_info().unwrap();
+            let mut data = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut data).unwrap();
+
+            assert_eq!(dimensions, (info.width, info.height));
+            assert_eq!(reds.to_vec(), data.iter().step_by(3).copied().collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::vector::Scalar;
+use crate::vector::Vector3;
+
+/// A set of primary colors (inks) printed over a white paper, mixing
+/// subtractively, whose colors are made of f32 values unless otherwise
+/// specified.
+#[derive(Clone, Debug)]
+pub struct Inks<T = f32> {
+    /// The color of the bare paper, in XYZ space.
+    pub white: Vector3<T>,
+    /// The colors of the inks at full coverage, in XYZ space.
+    pub primaries: Vec<Vector3<T>>,
+    /// The maximum total coverage of all of the inks combined.
+    pub limit: f32,
+}
+
+impl<T: Scalar> Inks<T> {
+    /// Returns the color, in XYZ space, of the mixture of inks with the given
+    /// coverage fractions.
+    pub fn mix(&self, fractions: &[f32]) -> Vector3<T> {
+        let mut secondary = self.white;
+
+        for (primary, fraction) in self.primaries.iter().zip(fractions) {
+            let fraction = T::from_f32(*fraction);
+
+            secondary *= (*primary * fraction + self.white * (T::one() - fraction)) / self.white;
+        }
+
+        secondary
+    }
+
+    /// Moves the given coverage fractions to the nearest fractions that lie
+    /// between 0 and 1 and respect the ink limit.
+    pub fn constrain(&self, fractions: &mut [f32]) {
+        for fraction in fractions.iter_mut() {
+            *fraction = fraction.clamp(0.0, 1.0);
+        }
+
+        if fractions.iter().sum::<f32>() <= self.limit {
+            return;
+        }
+
+        // The nearest point on the ink limit's face of the unit hypercube is
+        // found by lowering every fraction by the same amount (clamping at 0),
+        // an amount which bisection can find just fine.
+        let mut low = 0.0;
+        let mut high = 1.0;
+        for _ in 0..32 {
+            let middle = 0.5 * (low + high);
+
+            let total = fractions.iter().map(|fraction| (fraction - middle).max(0.0)).sum::<f32>();
+
+            if total > self.limit {
+                low = middle;
+            }
+            else {
+                high = middle;
+            }
+        }
+
+        for fraction in fractions.iter_mut() {
+            *fraction = (*fraction - high).max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    /// Tests that the mixture of no ink is the paper and that the mixture of a
+    /// single ink at full coverage is that ink.
+    #[test]
+    fn test_mix() {
+        let inks = testing::inks(3, f32::INFINITY);
+
+        assert_eq!(inks.white, inks.mix(&[0.0, 0.0, 0.0]));
+
+        let secondary = inks.mix(&[0.0, 1.0, 0.0]);
+        for index in 0..3 {
+            assert!((secondary[index] - inks.primaries[1][index]).abs() <= 1e-6);
+        }
+    }
+
+    /// Tests that mixing in double precision agrees with mixing in single
+    /// precision.
+    #[test]
+    fn test_mix_double() {
+        let inks = testing::inks(3, f32::INFINITY);
+        let inks_double = Inks {
+            white: inks.white.cast::<f64>(),
+            primaries: inks.primaries.iter().map(|primary| primary.cast::<f64>()).collect(),
+            limit: inks.limit,
+        };
+
+        for fractions in [[0.0, 0.0, 0.0], [0.25, 0.5, 0.75], [1.0, 1.0, 1.0]] {
+            let secondary = inks.mix(&fractions);
+            let secondary_double = inks_double.mix(&fractions);
+
+            for index in 0..3 {
+                assert!((secondary[index] as f64 - secondary_double[index]).abs() <= 1e-6);
+            }
+        }
+    }
+
+    /// Tests that constrained fractions respect the bounds and the ink limit.
+    #[test]
+    fn test_constrain() {
+        let inks = testing::inks(3, 1.5);
+
+        let mut fractions = [1.2, 0.9, -0.3];
+        inks.constrain(&mut fractions);
+
+        assert!((fractions.iter().sum::<f32>() - 1.5).abs() <= 1e-5);
+        assert!((fractions[0] - 0.8).abs() <= 1e-5);
+        assert!((fractions[1] - 0.7).abs() <= 1e-5);
+        assert_eq!(0.0, fractions[2]);
+
+        let mut fractions = [0.2, 0.3, 0.4];
+        inks.constrain(&mut fractions);
+
+        assert_eq!([0.2, 0.3, 0.4], fractions);
+    }
+}
@@ -0,0 +1,788 @@
+use std::ffi::OsStr;
+use std::io;
+use std::io::Write;
+
+use crate::icc;
+use crate::icc::Icc;
+use crate::icc::Nclr;
+use crate::image;
+use crate::image::Hald;
+use crate::image::Layout;
+use crate::image::Strip;
+use crate::optimize::Interpolation;
+use crate::shader;
+use crate::shader::Language;
+use crate::shader::Shader;
+use crate::vector::Precision;
+use crate::vector::Vector3;
+
+/// The bit depth of the input values on the mesh line of `.3dl` files, which
+/// is what Autodesk's applications expect.
+const DEPTH_MESH: u32 = 10;
+
+/// The default bit depth of the colors of `.3dl` files.
+pub const DEPTH_AUTODESK_DEFAULT: u32 = 12;
+
+/// The bit depths that the colors of `.3dl` files can have.
+pub const DEPTHS_AUTODESK: [u32; 3] = [10, 12, 16];
+
+/// A file format that 3D LUTs can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// The Resolve/Adobe `.cube` format.
+    Cube,
+    /// The Autodesk (Lustre/Flame) `.3dl` format, whose colors are integers of
+    /// the given bit depth.
+    Autodesk {
+        depth: u32,
+    },
+    /// The Academy Common LUT Format, whose 3D LUT node is preceded by a 1D
+    /// LUT node that clamps colors to its domain if there's a shaper, and is
+    /// meant to be interpolated with the given interpolation.
+    Clf {
+        shaper: bool,
+        interpolation: Interpolation,
+    },
+    /// The Cinespace `.csp` format, whose 3D LUT is preceded by an identity
+    /// pre-LUT.
+    Cinespace,
+    /// The Nuke `.vf` vector field format.
+    Nuke,
+    /// Hald CLUT PNG images, whose colors have the given bit depth, or 16 bits
+    /// for masks and 8 bits otherwise if there's none.
+    Hald {
+        depth: Option<u32>,
+    },
+    /// Strip PNG images of the given layout, flipped if asked for, as game
+    /// engines take 2D LUT textures, whose colors have the given bit depth, or
+    /// 16 bits for masks and 8 bits otherwise if there's none.
+    Strip {
+        layout: Layout,
+        flip: bool,
+        depth: Option<u32>,
+    },
+    /// Fragment shader modules in the given shading language, which hold every
+    /// 3D LUT of a color separation at once.
+    Shader {
+        language: Language,
+    },
+    /// ICC profiles: RGB→RGB device links, or abstract Lab→Lab profiles if
+    /// asked for.
+    Icc {
+        abstract_lab: bool,
+    },
+    /// ICC output profiles whose device space is the inks of the primary
+    /// colors, which hold every mask of a color separation at once.
+    Nclr,
+}
+
+/// The options of formats that can be given on the command line, each of which
+/// only some formats take.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Options {
+    /// The bit depth of the colors of 3dl, hald and strip files.
+    pub depth: Option<u32>,
+    /// Whether CLF files have a shaper.
+    pub shaper: bool,
+    /// How CLF files are meant to be interpolated, if at all.
+    pub interpolation: Option<Interpolation>,
+    /// The layout of strip images.
+    pub layout: Option<Layout>,
+    /// Whether strip images are flipped.
+    pub flip: bool,
+    /// Whether ICC profiles are abstract profiles.
+    pub abstract_lab: bool,
+}
+
+/// What a 3D LUT is of and how it was generated, for formats that record it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    /// The name of the 3D LUT.
+    pub name: String,
+    /// A description of the 3D LUT.
+    pub description: String,
+    /// Details of how the 3D LUT was generated, each a name and a value.
+    pub info: Vec<(String, String)>,
+    /// Whether the 3D LUT is a mask, whose colors are all grays.
+    pub mask: bool,
+    /// The ICC profile whose space the 3D LUT's colors are in.
+    pub profile: Vec<u8>,
+}
+
+impl Format {
+    /// Returns the format of files with the given extension, if it's the
+    /// extension of any.
+    pub fn from_extension(extension: &OsStr) -> Option<Self> {
+        match extension.to_ascii_lowercase().to_str()? {
+            "cube" => Some(Format::Cube),
+            "3dl" => Some(Format::Autodesk { depth: DEPTH_AUTODESK_DEFAULT }),
+            "clf" => Some(Format::Clf { shaper: false, interpolation: Interpolation::Trilinear }),
+            "csp" => Some(Format::Cinespace),
+            "vf" => Some(Format::Nuke),
+            "png" => Some(Format::Hald { depth: None }),
+            "glsl" => Some(Format::Shader { language: Language::Glsl }),
+            "hlsl" => Some(Format::Shader { language: Language::Hlsl }),
+            "wgsl" => Some(Format::Shader { language: Language::Wgsl }),
+            "icc" | "icm" => Some(Format::Icc { abstract_lab: false }),
+            _ => None,
+        }
+    }
+
+    /// Returns this format with the given options, or a message saying which
+    /// of them this format doesn't take.
+    pub fn with_options(self, options: Options) -> Result<Self, String> {
+        let format = match (self, options.depth) {
+            (Format::Autodesk { .. }, Some(depth)) if DEPTHS_AUTODESK.contains(&depth) => Format::Autodesk { depth },
+            (Format::Autodesk { .. }, Some(_)) => return Err(String::from("Output depth must be 10, 12 or 16 bits for the 3dl format.")),
+            (Format::Hald { .. }, Some(depth)) if image::DEPTHS.contains(&depth) => Format::Hald { depth: Some(depth) },
+            (Format::Hald { .. }, Some(_)) => return Err(String::from("Output depth must be 8 or 16 bits for the hald format.")),
+            (Format::Strip { layout, flip, .. }, Some(depth)) if image::DEPTHS.contains(&depth) => Format::Strip { layout, flip, depth: Some(depth) },
+            (Format::Strip { .. }, Some(_)) => return Err(String::from("Output depth must be 8 or 16 bits for the strip format.")),
+            (_, Some(_)) => return Err(String::from("The output depth can only be used with the 3dl, hald and strip formats.")),
+            (format, None) => format,
+        };
+
+        if options.shaper && !matches!(format, Format::Clf { .. }) {
+            return Err(String::from("The shaper can only be used with the clf format."));
+        }
+        if options.layout.is_some() && !matches!(format, Format::Strip { .. }) {
+            return Err(String::from("The strip layout can only be used with the strip format."));
+        }
+        if options.flip && !matches!(format, Format::Strip { .. }) {
+            return Err(String::from("Flipping can only be used with the strip format."));
+        }
+        if options.abstract_lab && !matches!(format, Format::Icc { .. }) {
+            return Err(String::from("Abstract profiles can only be used with the icc format."));
+        }
+
+        Ok(match format {
+            // CLF files record how they're meant to be interpolated.
+            Format::Clf { .. } => Format::Clf { shaper: options.shaper, interpolation: options.interpolation.unwrap_or(Interpolation::Trilinear) },
+            // Strip images are laid out horizontally unless specified
+            // otherwise.
+            Format::Strip { depth, .. } => Format::Strip { layout: options.layout.unwrap_or(Layout::Horizontal), flip: options.flip, depth },
+            Format::Icc { .. } => Format::Icc { abstract_lab: options.abstract_lab },
+            format => format,
+        })
+    }
+
+    /// Checks that this format can hold the 3D LUTs of the given sizes of a
+    /// color separation of the given number of primary colors, returning a
+    /// message saying why not otherwise.
+    pub fn check(self, count_primaries: usize, sizes: &[usize]) -> Result<(), String> {
+        match self {
+            Format::Hald { .. } if sizes.iter().any(|size| image::level(*size).is_none()) =>
+                Err(String::from("3D LUT sizes must be squares of integers (e.g. 16, 36 or 64) for the hald format.")),
+            Format::Shader { .. } if sizes.iter().any(|size| *size > shader::SIZE_MAX) =>
+                Err(format!("3D LUT sizes must be at most {} for shader modules.", shader::SIZE_MAX)),
+            Format::Nclr if !(2..=icc::COUNT_INKS_MAX).contains(&count_primaries) =>
+                Err(format!("The nclr format needs between 2 and {} primary colors.", icc::COUNT_INKS_MAX)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a writer of the 3D LUTs of a color separation in this format,
+    /// for inks of the given primary colors, in the profile's space and between
+    /// 0 and 1, whose colors are written with as many digits as the given
+    /// precision calls for.
+    ///
+    /// Outputs are made by the given function, along with the metadata that
+    /// they record if the format records anything: the output of the 3D LUT
+    /// with the given index, in the usual order, or the output of every 3D LUT
+    /// at once if there's no index, for formats that hold them all.
+    pub fn writer<W: Write + 'static>(self, mut output: impl FnMut(Option<usize>) -> (W, Metadata), precision: Precision, primaries: Vec<Vector3<f64>>) -> Box<dyn SeparationWriter> {
+        // Writes each 3D LUT to an output of its own with a writer made by the
+        // given function.
+        let mut files = |writer: &dyn Fn(W, Metadata) -> Box<dyn LutWriter>| -> Box<dyn SeparationWriter> {
+            Box::new(Files {
+                writers: (0..1 + 2 * primaries.len()).map(|index| {
+                    let (output, metadata) = output(Some(index));
+
+                    writer(output, metadata)
+                }).collect(),
+            })
+        };
+
+        match self {
+            Format::Cube => files(&|output, _| Box::new(Cube::new(output, precision))),
+            Format::Autodesk { depth } => files(&|output, _| Box::new(Autodesk::new(output, depth))),
+            Format::Clf { shaper, interpolation } => files(&|output, metadata| Box::new(Clf::new(output, precision, metadata, shaper, interpolation))),
+            Format::Cinespace => files(&|output, metadata| Box::new(Cinespace::new(output, precision, metadata))),
+            Format::Nuke => files(&|output, _| Box::new(Nuke::new(output, precision))),
+            // Masks are smooth gradients, which 8 bits would band.
+            Format::Hald { depth } => files(&|output, metadata| Box::new(Hald::new(output, depth.unwrap_or(if metadata.mask { 16 } else { 8 })))),
+            Format::Strip { layout, flip, depth } => files(&|output, metadata| Box::new(Strip::new(output, layout, flip, depth.unwrap_or(if metadata.mask { 16 } else { 8 })))),
+            Format::Icc { abstract_lab } => files(&|output, metadata| Box::new(Icc::new(output, metadata, abstract_lab))),
+            Format::Shader { language } => {
+                let (output, metadata) = output(None);
+
+                Box::new(Shader::new(output, language, metadata, primaries))
+            },
+            Format::Nclr => {
+                let (output, metadata) = output(None);
+
+                Box::new(Nclr::new(output, metadata, primaries))
+            },
+        }
+    }
+}
+
+/// A writer of a 3D LUT in some file format.
+///
+/// A 3D LUT is begun, then its colors are handed over in their usual order, red
+/// fastest and blue slowest, in as many pieces as need be, then it's finished.
+/// Writers of formats that order colors otherwise must hold on to them until
+/// the 3D LUT is finished.
+pub trait LutWriter {
+    /// Begins a 3D LUT of the given size.
+    fn begin(&mut self, size: usize) -> io::Result<()>;
+
+    /// Writes the given colors of the 3D LUT, carrying on from the last ones.
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()>;
+
+    /// Finishes the 3D LUT, flushing it to the output.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A writer of every 3D LUT of a color separation at once, in some file format
+/// that holds them all: the composite, then the primary and mask of each
+/// primary color, in that order.
+///
+/// The 3D LUTs are begun, then their colors are handed over as they would be
+/// to a `LutWriter` of each, then they're finished.
+pub trait SeparationWriter {
+    /// Begins the 3D LUTs, which are all of the given size.
+    fn begin(&mut self, size: usize) -> io::Result<()>;
+
+    /// Writes the given colors of the 3D LUT with the given index, carrying on
+    /// from its last ones.
+    fn write_colors(&mut self, index: usize, colors: &[Vector3<f64>]) -> io::Result<()>;
+
+    /// Finishes the 3D LUTs, flushing them to the output.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A writer of the 3D LUTs of a color separation to outputs of their own, each
+/// through a writer of its own.
+struct Files {
+    writers: Vec<Box<dyn LutWriter>>,
+}
+
+impl SeparationWriter for Files {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        for writer in self.writers.iter_mut() {
+            writer.begin(size)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, index: usize, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.writers[index].write_colors(colors)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        for writer in self.writers.iter_mut() {
+            writer.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A writer of 3D LUTs in the Resolve/Adobe `.cube` format: a header giving
+/// the size and domain, then a line for each color.
+pub struct Cube<W: Write> {
+    output: W,
+    precision: Precision,
+}
+
+impl<W: Write> Cube<W> {
+    /// Returns a writer to the given output, whose colors are written with as
+    /// many digits as the given precision calls for.
+    pub fn new(output: W, precision: Precision) -> Self {
+        Self {
+            output,
+            precision,
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Cube<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        writeln!(self.output, "LUT_3D_SIZE {}", size)?;
+        writeln!(self.output, "DOMAIN_MIN 0 0 0")?;
+        writeln!(self.output, "DOMAIN_MAX 1 1 1")
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        for color in colors {
+            let color = color.0.map(|component| component.clamp(0.0, 1.0));
+
+            match self.precision {
+                Precision::Single => writeln!(self.output, "{} {} {}", color[0] as f32, color[1] as f32, color[2] as f32)?,
+                Precision::Double => writeln!(self.output, "{} {} {}", color[0], color[1], color[2])?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// A writer of 3D LUTs in the Autodesk (Lustre/Flame) `.3dl` format: a mesh
+/// line giving the input values of the nodes along each axis, then a line for
+/// each color with its components scaled to integers, blue fastest.
+pub struct Autodesk<W: Write> {
+    output: W,
+    depth: u32,
+    size: usize,
+    colors: Vec<Vector3<f64>>,
+}
+
+impl<W: Write> Autodesk<W> {
+    /// Returns a writer to the given output, whose colors are written as
+    /// integers of the given bit depth.
+    pub fn new(output: W, depth: u32) -> Self {
+        Self {
+            output,
+            depth,
+            size: 0,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Autodesk<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        self.colors = Vec::with_capacity(size.pow(3));
+
+        let maximum = ((1 << DEPTH_MESH) - 1) as f64;
+        let mesh = (0..size)
+            .map(|index| (index as f64 * maximum / (size - 1) as f64).round().to_string())
+            .collect::<Vec<_>>();
+
+        writeln!(self.output, "{}", mesh.join(" "))
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.colors.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let size = self.size;
+        let maximum = ((1_u64 << self.depth) - 1) as f64;
+
+        for index_red in 0..size {
+            for index_green in 0..size {
+                for index_blue in 0..size {
+                    let color = self.colors[(index_blue * size + index_green) * size + index_red];
+                    let color = color.0.map(|component| (component.clamp(0.0, 1.0) * maximum).round() as u64);
+
+                    writeln!(self.output, "{} {} {}", color[0], color[1], color[2])?;
+                }
+            }
+        }
+
+        self.colors = Vec::new();
+
+        self.output.flush()
+    }
+}
+
+/// A writer of 3D LUTs in the Academy Common LUT Format: an XML process list
+/// with a description and details of the 3D LUT, then possibly a shaper 1D LUT
+/// node, then a 3D LUT node with the colors, blue fastest.
+pub struct Clf<W: Write> {
+    output: W,
+    precision: Precision,
+    metadata: Metadata,
+    shaper: bool,
+    interpolation: Interpolation,
+    size: usize,
+    colors: Vec<Vector3<f64>>,
+}
+
+impl<W: Write> Clf<W> {
+    /// Returns a writer to the given output, whose colors are written with as
+    /// many digits as the given precision calls for, recording the given
+    /// metadata, with a shaper 1D LUT node if asked for, and meant to be
+    /// interpolated with the given interpolation.
+    pub fn new(output: W, precision: Precision, metadata: Metadata, shaper: bool, interpolation: Interpolation) -> Self {
+        Self {
+            output,
+            precision,
+            metadata,
+            shaper,
+            interpolation,
+            size: 0,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Clf<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        self.colors = Vec::with_capacity(size.pow(3));
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        self.colors.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let size = self.size;
+        let name = escape(&self.metadata.name);
+
+        writeln!(self.output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(self.output, r#"<ProcessList id="{}" name="{}" compCLFversion="3">"#, name, name)?;
+        writeln!(self.output, "    <Description>{}</Description>", escape(&self.metadata.description))?;
+
+        writeln!(self.output, "    <Info>")?;
+        for (key, value) in self.metadata.info.iter() {
+            writeln!(self.output, "        <{}>{}</{}>", key, escape(value), key)?;
+        }
+        writeln!(self.output, "    </Info>")?;
+
+        // Input outside of a 1D LUT's domain is clamped to it.
+        if self.shaper {
+            writeln!(self.output, r#"    <LUT1D id="shaper" name="Shaper" inBitDepth="32f" outBitDepth="32f">"#)?;
+            writeln!(self.output, "        <Description>Clamps colors to the domain of the 3D LUT.</Description>")?;
+            writeln!(self.output, r#"        <Array dim="2 1">"#)?;
+            writeln!(self.output, "0")?;
+            writeln!(self.output, "1")?;
+            writeln!(self.output, "        </Array>")?;
+            writeln!(self.output, "    </LUT1D>")?;
+        }
+
+        let interpolation = match self.interpolation {
+            Interpolation::Trilinear => "trilinear",
+            Interpolation::Tetrahedral => "tetrahedral",
+        };
+
+        writeln!(self.output, r#"    <LUT3D id="lut" name="{}" inBitDepth="32f" outBitDepth="32f" interpolation="{}">"#, name, interpolation)?;
+        writeln!(self.output, r#"        <Array dim="{} {} {} 3">"#, size, size, size)?;
+
+        for index_red in 0..size {
+            for index_green in 0..size {
+                for index_blue in 0..size {
+                    let color = self.colors[(index_blue * size + index_green) * size + index_red];
+                    let color = color.0.map(|component| component.clamp(0.0, 1.0));
+
+                    match self.precision {
+                        Precision::Single => writeln!(self.output, "{} {} {}", color[0] as f32, color[1] as f32, color[2] as f32)?,
+                        Precision::Double => writeln!(self.output, "{} {} {}", color[0], color[1], color[2])?,
+                    }
+                }
+            }
+        }
+
+        writeln!(self.output, "        </Array>")?;
+        writeln!(self.output, "    </LUT3D>")?;
+        writeln!(self.output, "</ProcessList>")?;
+
+        self.colors = Vec::new();
+
+        self.output.flush()
+    }
+}
+
+/// A writer of 3D LUTs in the Cinespace `.csp` format: a header with the
+/// metadata, then an identity pre-LUT for each channel that clamps colors to
+/// the domain of the 3D LUT, then the size and a line for each color.
+pub struct Cinespace<W: Write> {
+    output: W,
+    precision: Precision,
+    metadata: Metadata,
+}
+
+impl<W: Write> Cinespace<W> {
+    /// Returns a writer to the given output, whose colors are written with as
+    /// many digits as the given precision calls for, recording the given
+    /// metadata.
+    pub fn new(output: W, precision: Precision, metadata: Metadata) -> Self {
+        Self {
+            output,
+            precision,
+            metadata,
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Cinespace<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        writeln!(self.output, "CSPLUTV100")?;
+        writeln!(self.output, "3D")?;
+        writeln!(self.output)?;
+
+        writeln!(self.output, "BEGIN METADATA")?;
+        writeln!(self.output, "{}", self.metadata.name)?;
+        writeln!(self.output, "{}", self.metadata.description)?;
+        for (key, value) in self.metadata.info.iter() {
+            writeln!(self.output, "{}: {}", key, value)?;
+        }
+        writeln!(self.output, "END METADATA")?;
+        writeln!(self.output)?;
+
+        // Each pre-LUT is its number of points, their inputs and their
+        // outputs.
+        for _ in 0..3 {
+            writeln!(self.output, "2")?;
+            writeln!(self.output, "0 1")?;
+            writeln!(self.output, "0 1")?;
+        }
+        writeln!(self.output)?;
+
+        writeln!(self.output, "{} {} {}", size, size, size)
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        for color in colors {
+            let color = color.0.map(|component| component.clamp(0.0, 1.0));
+
+            match self.precision {
+                Precision::Single => writeln!(self.output, "{} {} {}", color[0] as f32, color[1] as f32, color[2] as f32)?,
+                Precision::Double => writeln!(self.output, "{} {} {}", color[0], color[1], color[2])?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// A writer of 3D LUTs in the Nuke `.vf` vector field format: an Inventor
+/// header giving the size and an identity transform, then a line for each
+/// color.
+pub struct Nuke<W: Write> {
+    output: W,
+    precision: Precision,
+}
+
+impl<W: Write> Nuke<W> {
+    /// Returns a writer to the given output, whose colors are written with as
+    /// many digits as the given precision calls for.
+    pub fn new(output: W, precision: Precision) -> Self {
+        Self {
+            output,
+            precision,
+        }
+    }
+}
+
+impl<W: Write> LutWriter for Nuke<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        writeln!(self.output, "#Inventor V2.1 ascii")?;
+        writeln!(self.output, "grid_size {} {} {}", size, size, size)?;
+        writeln!(self.output, "global_transform 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1")?;
+        writeln!(self.output, "data")
+    }
+
+    fn write_colors(&mut self, colors: &[Vector3<f64>]) -> io::Result<()> {
+        for color in colors {
+            let color = color.0.map(|component| component.clamp(0.0, 1.0));
+
+            match self.precision {
+                Precision::Single => writeln!(self.output, "{} {} {}", color[0] as f32, color[1] as f32, color[2] as f32)?,
+                Precision::Double => writeln!(self.output, "{} {} {}", color[0], color[1], color[2])?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Escapes the given text for use in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::OsString;
+
+    /// Tests that formats are told apart by extension regardless of case.
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(Some(Format::Cube), Format::from_extension(&OsString::from("cube")));
+        assert_eq!(Some(Format::Cube), Format::from_extension(&OsString::from("CUBE")));
+        assert_eq!(Some(Format::Autodesk { depth: DEPTH_AUTODESK_DEFAULT }), Format::from_extension(&OsString::from("3DL")));
+        assert_eq!(Some(Format::Clf { shaper: false, interpolation: Interpolation::Trilinear }), Format::from_extension(&OsString::from("clf")));
+        assert_eq!(Some(Format::Cinespace), Format::from_extension(&OsString::from("csp")));
+        assert_eq!(Some(Format::Nuke), Format::from_extension(&OsString::from("VF")));
+        assert_eq!(Some(Format::Hald { depth: None }), Format::from_extension(&OsString::from("png")));
+        assert_eq!(Some(Format::Shader { language: Language::Wgsl }), Format::from_extension(&OsString::from("wgsl")));
+        assert_eq!(Some(Format::Icc { abstract_lab: false }), Format::from_extension(&OsString::from("ICM")));
+        assert_eq!(None, Format::from_extension(&OsString::from("txt")));
+    }
+
+    /// Tests that formats take the options that apply to them, and reject the
+    /// rest along with what they can't hold.
+    #[test]
+    fn test_with_options() {
+        let clf = Format::Clf { shaper: false, interpolation: Interpolation::Trilinear };
+        let options = Options {
+            shaper: true,
+            interpolation: Some(Interpolation::Tetrahedral),
+            ..Options::default()
+        };
+
+        assert_eq!(Ok(Format::Clf { shaper: true, interpolation: Interpolation::Tetrahedral }), clf.with_options(options));
+        assert!(Format::Cube.with_options(options).is_err());
+        assert_eq!(Ok(Format::Cube), Format::Cube.with_options(Options { interpolation: Some(Interpolation::Tetrahedral), ..Options::default() }));
+
+        assert_eq!(Ok(Format::Autodesk { depth: 16 }), Format::Autodesk { depth: DEPTH_AUTODESK_DEFAULT }.with_options(Options { depth: Some(16), ..Options::default() }));
+        assert!(Format::Autodesk { depth: DEPTH_AUTODESK_DEFAULT }.with_options(Options { depth: Some(8), ..Options::default() }).is_err());
+        assert!(Format::Nuke.with_options(Options { depth: Some(8), ..Options::default() }).is_err());
+
+        assert!(Format::Hald { depth: None }.check(1, &[16, 36]).is_ok());
+        assert!(Format::Hald { depth: None }.check(1, &[16, 33]).is_err());
+        assert!(Format::Shader { language: Language::Glsl }.check(1, &[9, 17]).is_ok());
+        assert!(Format::Shader { language: Language::Glsl }.check(1, &[9, 64]).is_err());
+        assert!(Format::Nclr.check(1, &[33]).is_err());
+        assert!(Format::Nclr.check(4, &[33]).is_ok());
+    }
+
+    /// Tests the layout of `.cube` files, with colors clamped to the domain.
+    #[test]
+    fn test_cube() {
+        let mut output = Vec::new();
+
+        let mut writer = Cube::new(&mut output, Precision::Single);
+        writer.begin(2).unwrap();
+        writer.write_colors(&[Vector3([0.0, 0.5, 1.0]), Vector3([-0.5, 0.25, 1.5])]).unwrap();
+        writer.write_colors(&[Vector3([1.0, 1.0, 1.0])]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 1 1\n0 0.5 1\n0 0.25 1\n1 1 1\n", String::from_utf8(output).unwrap());
+    }
+
+    /// Tests the layout of `.3dl` files, with colors scaled to the bit depth,
+    /// clamped, and reordered blue fastest.
+    #[test]
+    fn test_autodesk() {
+        let mut output = Vec::new();
+
+        let colors = (0..8).map(|index| Vector3([(index & 1) as f64, (index >> 1 & 1) as f64, (index >> 2 & 1) as f64 * 1.5])).collect::<Vec<_>>();
+
+        let mut writer = Autodesk::new(&mut output, 10);
+        writer.begin(2).unwrap();
+        writer.write_colors(&colors[..3]).unwrap();
+        writer.write_colors(&colors[3..]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            "0 1023\n0 0 0\n0 0 1023\n0 1023 0\n0 1023 1023\n1023 0 0\n1023 0 1023\n1023 1023 0\n1023 1023 1023\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut output = Vec::new();
+
+        let mut writer = Autodesk::new(&mut output, 16);
+        writer.begin(3).unwrap();
+        writer.write_colors(&vec![Vector3([0.5, 0.5, 0.5]); 27]).unwrap();
+        writer.finish().unwrap();
+
+        let lines = String::from_utf8(output).unwrap().lines().map(String::from).collect::<Vec<_>>();
+
+        assert_eq!(28, lines.len());
+        assert_eq!("0 512 1023", lines[0]);
+        assert_eq!("32768 32768 32768", lines[1]);
+    }
+
+    /// Tests the layout of CLF files, with their metadata escaped and their
+    /// colors reordered blue fastest.
+    #[test]
+    fn test_clf() {
+        let mut output = Vec::new();
+
+        let metadata = Metadata {
+            name: String::from("output_0"),
+            description: String::from("Ink 0 of <R&D>"),
+            info: vec![(String::from("Profile"), String::from("\"Custom\".icc"))],
+            mask: false,
+            profile: Vec::new(),
+        };
+        let colors = (0..8).map(|index| Vector3([(index & 1) as f64, (index >> 1 & 1) as f64, (index >> 2 & 1) as f64 * 0.5])).collect::<Vec<_>>();
+
+        let mut writer = Clf::new(&mut output, Precision::Single, metadata, true, Interpolation::Tetrahedral);
+        writer.begin(2).unwrap();
+        writer.write_colors(&colors).unwrap();
+        writer.finish().unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ProcessList id=\"output_0\" name=\"output_0\" compCLFversion=\"3\">\n"));
+        assert!(output.contains("<Description>Ink 0 of &lt;R&amp;D&gt;</Description>"));
+        assert!(output.contains("<Profile>&quot;Custom&quot;.icc</Profile>"));
+        assert!(output.find("<LUT1D").unwrap() < output.find("<LUT3D").unwrap());
+        assert!(output.contains("interpolation=\"tetrahedral\">\n        <Array dim=\"2 2 2 3\">\n0 0 0\n0 0 0.5\n0 1 0\n"));
+        assert!(output.ends_with("1 1 0.5\n        </Array>\n    </LUT3D>\n</ProcessList>\n"));
+    }
+
+    /// Tests the layout of `.csp` files, with their metadata and pre-LUTs
+    /// ahead of the colors, which are clamped to the domain.
+    #[test]
+    fn test_cinespace() {
+        let mut output = Vec::new();
+
+        let metadata = Metadata {
+            name: String::from("output_0m"),
+            description: String::from("Mask of ink 0"),
+            info: vec![(String::from("Profile"), String::from("sRGB"))],
+            mask: true,
+            profile: Vec::new(),
+        };
+
+        let mut writer = Cinespace::new(&mut output, Precision::Single, metadata);
+        writer.begin(2).unwrap();
+        writer.write_colors(&[Vector3([0.0, 0.5, 1.0]), Vector3([-0.5, 0.25, 1.5])]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            "CSPLUTV100\n3D\n\nBEGIN METADATA\noutput_0m\nMask of ink 0\nProfile: sRGB\nEND METADATA\n\n\
+            2\n0 1\n0 1\n2\n0 1\n0 1\n2\n0 1\n0 1\n\n2 2 2\n0 0.5 1\n0 0.25 1\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    /// Tests the layout of `.vf` files, with colors clamped to the domain.
+    #[test]
+    fn test_nuke() {
+        let mut output = Vec::new();
+
+        let mut writer = Nuke::new(&mut output, Precision::Single);
+        writer.begin(2).unwrap();
+        writer.write_colors(&[Vector3([0.0, 0.5, 1.0]), Vector3([-0.5, 0.25, 1.5])]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            "#Inventor V2.1 ascii\ngrid_size 2 2 2\nglobal_transform 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1\ndata\n0 0.5 1\n0 0.25 1\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
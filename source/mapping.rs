@@ -0,0 +1,295 @@
+use rstar::primitives::GeomWithData;
+use rstar::PointDistance;
+use rstar::RTree;
+
+use crate::vector::Vector3;
+
+/// The CIELCh weights used by the lightness-preserving mapping mode.
+pub const WEIGHTS_LIGHTNESS: Vector3 = Vector3([1.0, 0.25, 0.25]);
+
+/// The CIELCh weights used by the hue-preserving mapping mode.
+pub const WEIGHTS_HUE: Vector3 = Vector3([0.25, 0.25, 1.0]);
+
+/// The CIELCh weights used by the plain CIELAB mapping mode, under which the
+/// distance between two colors is their CIE76 color difference.
+pub const WEIGHTS_LAB: Vector3 = Vector3([1.0, 1.0, 1.0]);
+
+/// A method of measuring the distance between colors, used to map each 3D LUT
+/// color onto its nearest secondary color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mapping {
+    /// Colors are compared by their Euclidean distance in XYZ space.
+    Colorimetric,
+    /// Colors are compared by a weighted distance between their CIELCh
+    /// lightness, chroma and hue components, in that order. All weights must
+    /// be positive.
+    Weighted(Vector3),
+}
+
+impl Mapping {
+    /// Returns the coordinates of the given XYZ color in the space that this
+    /// mapping measures distances in, relative to the given white point.
+    ///
+    /// For weighted mappings, the space is CIELAB with its lightness axis
+    /// scaled by the lightness weight and its other axes by the lesser of the
+    /// chroma and hue weights. Euclidean distances in that space never exceed
+    /// the weighted distance, and match it whenever the chroma and hue weights
+    /// are equal, which keeps nearest neighbor searches tight.
+    pub fn point(&self, color: Vector3, white: Vector3) -> Vector3 {
+        self.scale(match self {
+            Mapping::Colorimetric => color,
+            Mapping::Weighted(_) => lab(color, white),
+        })
+    }
+
+    /// Scales the given CIELAB coordinates into this mapping's space.
+    fn scale(&self, color: Vector3) -> Vector3 {
+        match self {
+            Mapping::Colorimetric => color,
+            Mapping::Weighted(weights) => {
+                let weight_ab = weights[1].min(weights[2]);
+
+                Vector3([weights[0], weight_ab, weight_ab]) * color
+            },
+        }
+    }
+
+    /// Scales the given point in this mapping's space back into CIELAB
+    /// coordinates.
+    fn unscale(&self, point: Vector3) -> Vector3 {
+        match self {
+            Mapping::Colorimetric => point,
+            Mapping::Weighted(weights) => {
+                let weight_ab = weights[1].min(weights[2]);
+
+                point / Vector3([weights[0], weight_ab, weight_ab])
+            },
+        }
+    }
+
+    /// Returns the squared distance between the given points, which must both
+    /// be in this mapping's space.
+    pub fn distance_squared(&self, point_a: Vector3, point_b: Vector3) -> f32 {
+        match self {
+            Mapping::Colorimetric => {
+                let difference = point_a - point_b;
+
+                difference[0].powi(2) + difference[1].powi(2) + difference[2].powi(2)
+            },
+            Mapping::Weighted(weights) => {
+                let point_a = self.unscale(point_a);
+                let point_b = self.unscale(point_b);
+
+                let difference = point_a - point_b;
+
+                let chroma_a = point_a[1].hypot(point_a[2]);
+                let chroma_b = point_b[1].hypot(point_b[2]);
+
+                let difference_lightness = difference[0].powi(2);
+                let difference_chroma = (chroma_a - chroma_b).powi(2);
+                // The hue difference is whatever isn't accounted for by the
+                // difference in chroma, which can dip below zero by rounding.
+                let difference_hue = (difference[1].powi(2) + difference[2].powi(2) - difference_chroma).max(0.0);
+
+                weights[0].powi(2) * difference_lightness
+                    + weights[1].powi(2) * difference_chroma
+                    + weights[2].powi(2) * difference_hue
+            },
+        }
+    }
+
+    /// Returns the difference between the given points, which must both be in
+    /// this mapping's space, as a vector whose squared length is the squared
+    /// distance between them.
+    pub fn residual(&self, point_a: Vector3, point_b: Vector3) -> Vector3 {
+        match self {
+            Mapping::Colorimetric => point_a - point_b,
+            Mapping::Weighted(weights) => {
+                let point_a = self.unscale(point_a);
+                let point_b = self.unscale(point_b);
+
+                let chroma_a = point_a[1].hypot(point_a[2]);
+                let chroma_b = point_b[1].hypot(point_b[2]);
+
+                let mut angle = point_a[2].atan2(point_a[1]) - point_b[2].atan2(point_b[1]);
+                if angle > std::f32::consts::PI {
+                    angle -= std::f32::consts::TAU;
+                }
+                else if angle < -std::f32::consts::PI {
+                    angle += std::f32::consts::TAU;
+                }
+
+                *weights * Vector3([
+                    point_a[0] - point_b[0],
+                    chroma_a - chroma_b,
+                    2.0 * (chroma_a * chroma_b).sqrt() * (0.5 * angle).sin()
+                ])
+            },
+        }
+    }
+
+    /// Returns the element of the given R-tree nearest to the given point under
+    /// this mapping. The R-tree's geometry and the point must both be in this
+    /// mapping's space.
+    pub fn nearest<'a, T>(&self, rtree: &'a RTree<GeomWithData<Vector3, T>>, point: Vector3) -> Option<&'a GeomWithData<Vector3, T>> {
+        match self {
+            Mapping::Colorimetric => rtree.nearest_neighbor(&point),
+            Mapping::Weighted(_) => self.nearest_k(rtree, point, 1).pop(),
+        }
+    }
+
+    /// Returns the given number of elements of the given R-tree nearest to the
+    /// given point under this mapping, nearest first. The R-tree's geometry and
+    /// the point must both be in this mapping's space.
+    pub fn nearest_k<'a, T>(&self, rtree: &'a RTree<GeomWithData<Vector3, T>>, point: Vector3, count: usize) -> Vec<&'a GeomWithData<Vector3, T>> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let mut nearest = Vec::<(f32, &GeomWithData<Vector3, T>)>::with_capacity(count + 1);
+
+        // The R-tree yields its elements in order of Euclidean distance, which
+        // never exceeds the mapping's distance, so once it passes the
+        // distances of all of the nearest elements found so far, nothing
+        // further along can be any nearer.
+        for candidate in rtree.nearest_neighbor_iter(&point) {
+            if nearest.len() == count && candidate.distance_2(&point) >= nearest[count - 1].0 {
+                break;
+            }
+
+            let distance = self.distance_squared(*candidate.geom(), point);
+
+            if nearest.len() < count || distance < nearest[count - 1].0 {
+                let index = nearest.partition_point(|(distance_other, _)| *distance_other <= distance);
+                nearest.insert(index, (distance, candidate));
+                nearest.truncate(count);
+            }
+        }
+
+        nearest.into_iter().map(|(_, candidate)| candidate).collect()
+    }
+}
+
+/// Converts the given XYZ color to CIELAB coordinates relative to the given
+/// white point.
+pub fn lab(color: Vector3, white: Vector3) -> Vector3 {
+    /// The CIELAB companding function.
+    fn f(t: f32) -> f32 {
+        const DELTA: f32 = 6.0 / 29.0;
+
+        if t > DELTA.powi(3) {
+            t.cbrt()
+        }
+        else {
+            t / (3.0 * DELTA.powi(2)) + 4.0 / 29.0
+        }
+    }
+
+    let x = f(color[0] / white[0]);
+    let y = f(color[1] / white[1]);
+    let z = f(color[2] / white[2]);
+
+    Vector3([
+        116.0 * y - 16.0,
+        500.0 * (x - y),
+        200.0 * (y - z)
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    use crate::testing::WHITE;
+
+    #[allow(clippy::unused_unit)]
+    #[test_case(WHITE, Vector3([100.0, 0.0, 0.0]) ; "white")]
+    #[test_case(Vector3([0.0, 0.0, 0.0]), Vector3([0.0, 0.0, 0.0]) ; "black")]
+    #[test_case(0.18 * WHITE, Vector3([49.4961, 0.0, 0.0]) ; "grey")]
+    fn test_lab(color: Vector3, reference: Vector3) {
+        const TOLERANCE: f32 = 0.0005;
+
+        let result = lab(color, WHITE);
+
+        for index in 0..3 {
+            assert!((reference[index] - result[index]).abs() <= TOLERANCE,
+                "Index {}: {} !~= {} (+/- {})",
+                index,
+                reference[index],
+                result[index],
+                TOLERANCE
+            );
+        }
+    }
+
+    /// Tests that a hue rotation at constant lightness and chroma registers
+    /// only as a hue difference.
+    #[test]
+    fn test_weighted_hue() {
+        let point_a = Vector3([50.0, 30.0, 0.0]);
+        let point_b = Vector3([50.0, 0.0, 30.0]);
+
+        let mapping_lightness = Mapping::Weighted(WEIGHTS_LIGHTNESS);
+        let mapping_hue = Mapping::Weighted(WEIGHTS_HUE);
+
+        let distance_lightness = mapping_lightness.distance_squared(mapping_lightness.scale(point_a), mapping_lightness.scale(point_b));
+        let distance_hue = mapping_hue.distance_squared(mapping_hue.scale(point_a), mapping_hue.scale(point_b));
+
+        assert!((distance_lightness - 0.0625 * 1800.0).abs() <= 0.01);
+        assert!((distance_hue - 1800.0).abs() <= 0.01);
+    }
+
+    /// Tests that the residual between two points agrees with their distance.
+    #[test]
+    fn test_residual() {
+        let point_a = Vector3([50.0, 30.0, -10.0]);
+        let point_b = Vector3([40.0, -5.0, 25.0]);
+
+        for mapping in [Mapping::Colorimetric, Mapping::Weighted(WEIGHTS_LIGHTNESS), Mapping::Weighted(WEIGHTS_HUE)] {
+            let point_a = mapping.scale(point_a);
+            let point_b = mapping.scale(point_b);
+
+            let residual = mapping.residual(point_a, point_b);
+            let distance = residual[0].powi(2) + residual[1].powi(2) + residual[2].powi(2);
+            let difference = point_a - point_b;
+
+            assert!((distance - mapping.distance_squared(point_a, point_b)).abs() <= 0.01);
+            assert!(difference[0].powi(2) + difference[1].powi(2) + difference[2].powi(2) <= distance + 0.01);
+        }
+    }
+
+    /// Tests that the weighted nearest neighbor searches agree with an
+    /// exhaustive search.
+    #[test]
+    fn test_nearest() {
+        let mapping = Mapping::Weighted(WEIGHTS_LIGHTNESS);
+
+        let mut points = Vec::new();
+        for index in 0..200 {
+            let index = index as f32;
+
+            points.push(GeomWithData::new(Vector3([
+                (index * 7.0) % 100.0,
+                (index * 13.0) % 200.0 - 100.0,
+                (index * 29.0) % 200.0 - 100.0,
+            ]), index));
+        }
+
+        let query = Vector3([42.0, -17.0, 33.0]);
+
+        let mut reference = points.clone();
+        reference.sort_by(|a, b|
+            mapping.distance_squared(*a.geom(), query).total_cmp(&mapping.distance_squared(*b.geom(), query))
+        );
+
+        let rtree = RTree::bulk_load(points);
+
+        assert_eq!(reference[0].data, mapping.nearest(&rtree, query).unwrap().data);
+
+        let result = mapping.nearest_k(&rtree, query, 5).into_iter().map(|point| point.data).collect::<Vec<_>>();
+
+        assert_eq!(reference[..5].iter().map(|point| point.data).collect::<Vec<_>>(), result);
+    }
+}
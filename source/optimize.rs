@@ -0,0 +1,242 @@
+use crate::vector::Scalar;
+
+/// The number of samples per edge of a 3D LUT cell that optimization fits the
+/// interpolated 3D LUT to, counting only one of its ends. Two puts a sample in
+/// the middle of every edge, face and cell, about where interpolation strays
+/// furthest from the separations, and every subdivision more would multiply
+/// the colors to separate by its cube for little gain.
+pub const SUBDIVISIONS: usize = 2;
+
+/// The maximum number of passes optimization makes over a 3D LUT.
+const ITERATIONS_MAX: usize = 16;
+
+/// The change in node values below which optimization considers a pass to have
+/// settled.
+const CHANGE_MIN: f32 = 1e-5;
+
+/// A method of interpolating between the colors of a 3D LUT, as used by the
+/// host applications that apply the 3D LUT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Trilinear interpolation between the 8 corners of a cell.
+    Trilinear,
+    /// Interpolation between the 4 corners of the tetrahedron containing the
+    /// point, out of the 6 that share the diagonal of a cell from its lowest to
+    /// its highest corner.
+    Tetrahedral,
+}
+
+impl Interpolation {
+    /// Returns the indices and weights of the nodes of a 3D LUT of the given
+    /// size that this interpolation blends together at the given point, whose
+    /// coordinates are in units of nodes. Nodes are indexed in the usual order
+    /// of a 3D LUT, red fastest.
+    pub fn weights(&self, size: usize, point: [f32; 3]) -> Vec<(usize, f32)> {
+        let mut base = [0; 3];
+        let mut offset = [0.0; 3];
+
+        for axis in 0..3 {
+            base[axis] = (point[axis].floor().max(0.0) as usize).min(size - 2);
+            offset[axis] = point[axis] - base[axis] as f32;
+        }
+
+        let index = |corner: [usize; 3]| -> usize {
+            (base[0] + corner[0]) + (base[1] + corner[1]) * size + (base[2] + corner[2]) * size * size
+        };
+
+        match self {
+            Interpolation::Trilinear => {
+                let mut weights = Vec::with_capacity(8);
+
+                for corner in 0..8 {
+                    let corner = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+
+                    let weight = (0..3).map(|axis| {
+                        if corner[axis] == 1 { offset[axis] } else { 1.0 - offset[axis] }
+                    }).product::<f32>();
+
+                    weights.push((index(corner), weight));
+                }
+
+                weights
+            },
+            Interpolation::Tetrahedral => {
+                // Walk from the lowest corner to the highest, stepping along
+                // the axes in order of decreasing offset.
+                let mut axes = [0, 1, 2];
+                axes.sort_by(|a, b| offset[*b].total_cmp(&offset[*a]));
+
+                let mut weights = Vec::with_capacity(4);
+                let mut corner = [0; 3];
+                let mut previous = 1.0;
+
+                for axis in axes {
+                    weights.push((index(corner), previous - offset[axis]));
+
+                    corner[axis] = 1;
+                    previous = offset[axis];
+                }
+
+                weights.push((index(corner), previous));
+
+                weights
+            },
+        }
+    }
+
+    /// Interpolates the given node values of a 3D LUT of the given size at the
+    /// given point, whose coordinates are in units of nodes.
+    pub fn interpolate<T: Scalar>(&self, size: usize, values: &[T], point: [f32; 3]) -> T {
+        self.weights(size, point).into_iter().map(|(index, weight)| T::from_f32(weight) * values[index]).sum()
+    }
+}
+
+/// Adjusts the given node values of a 3D LUT of the given size, kept between 0
+/// and 1, so that interpolating them with the given interpolation matches the
+/// given exact values at the samples of a grid `SUBDIVISIONS` times as fine as
+/// much as possible, in the least squares sense.
+///
+/// Each pass visits the nodes in turn, moving each one to the value that best
+/// fits the samples it affects given the other nodes' current values.
+pub fn optimize<T: Scalar>(interpolation: Interpolation, size: usize, values: &mut [T], samples: &[T]) {
+    let size_fine = SUBDIVISIONS * (size - 1) + 1;
+
+    let point = |index_sample: usize| -> [f32; 3] {
+        [
+            (index_sample % size_fine) as f32 / SUBDIVISIONS as f32,
+            (index_sample / size_fine % size_fine) as f32 / SUBDIVISIONS as f32,
+            (index_sample / (size_fine * size_fine)) as f32 / SUBDIVISIONS as f32,
+        ]
+    };
+
+    let mut residuals = (0..samples.len()).map(|index_sample|
+        interpolation.interpolate(size, values, point(index_sample)) - samples[index_sample]
+    ).collect::<Vec<_>>();
+
+    let mut support = Vec::new();
+
+    for _ in 0..ITERATIONS_MAX {
+        let mut change_max = T::zero();
+
+        for (index, value_node) in values.iter_mut().enumerate() {
+            let coordinates = [index % size, index / size % size, index / (size * size)];
+
+            // Gather the samples this node affects, which lie in the cells
+            // around it.
+            support.clear();
+            let range = |axis: usize| {
+                (SUBDIVISIONS * coordinates[axis]).saturating_sub(SUBDIVISIONS - 1)..=(SUBDIVISIONS * coordinates[axis] + SUBDIVISIONS - 1).min(size_fine - 1)
+            };
+
+            for z in range(2) {
+                for y in range(1) {
+                    for x in range(0) {
+                        let index_sample = (z * size_fine + y) * size_fine + x;
+
+                        for (index_node, weight) in interpolation.weights(size, point(index_sample)) {
+                            if index_node == index && weight > 0.0 {
+                                support.push((index_sample, weight));
+                            }
+                        }
+                    }
+                }
+            }
+
+            let numerator = support.iter().map(|(index_sample, weight)| T::from_f32(*weight) * residuals[*index_sample]).sum::<T>();
+            let denominator = support.iter().map(|(_, weight)| T::from_f32(weight.powi(2))).sum::<T>();
+
+            if denominator <= T::zero() {
+                continue;
+            }
+
+            let value = (*value_node - numerator / denominator).clamp(T::zero(), T::one());
+            let change = value - *value_node;
+
+            for (index_sample, weight) in support.iter() {
+                residuals[*index_sample] = residuals[*index_sample] + T::from_f32(*weight) * change;
+            }
+
+            *value_node = value;
+            change_max = change_max.max(change.abs());
+        }
+
+        if change_max < T::from_f32(CHANGE_MIN) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the interpolation weights form a partition of unity that
+    /// reproduces the point itself.
+    #[test]
+    fn test_weights() {
+        let size = 4;
+
+        for interpolation in [Interpolation::Trilinear, Interpolation::Tetrahedral] {
+            for point in [[0.0, 0.0, 0.0], [0.3, 1.7, 2.2], [3.0, 3.0, 3.0], [1.5, 0.25, 0.75]] {
+                let weights = interpolation.weights(size, point);
+
+                assert!((weights.iter().map(|(_, weight)| weight).sum::<f32>() - 1.0).abs() <= 1e-6);
+                assert!(weights.iter().all(|(_, weight)| *weight >= 0.0));
+
+                for axis in 0..3 {
+                    let stride = size.pow(axis as u32);
+                    let coordinate = weights.iter().map(|(index, weight)| (index / stride % size) as f32 * weight).sum::<f32>();
+
+                    assert!((coordinate - point[axis]).abs() <= 1e-5, "{:?} {:?}", interpolation, point);
+                }
+            }
+        }
+    }
+
+    /// Tests that optimization reduces the error of the interpolated 3D LUT
+    /// against a function that a 3D LUT can't represent exactly.
+    #[test]
+    fn test_optimize() {
+        let size = 5;
+        let size_fine = SUBDIVISIONS * (size - 1) + 1;
+
+        let function = |point: [f32; 3]| -> f32 {
+            let point = point.map(|coordinate| coordinate / (size - 1) as f32);
+
+            (point[0] * point[1] + point[2].powi(2)).min(1.0)
+        };
+
+        let samples = (0..size_fine.pow(3)).map(|index| function([
+            (index % size_fine) as f32 / SUBDIVISIONS as f32,
+            (index / size_fine % size_fine) as f32 / SUBDIVISIONS as f32,
+            (index / (size_fine * size_fine)) as f32 / SUBDIVISIONS as f32,
+        ])).collect::<Vec<_>>();
+
+        for interpolation in [Interpolation::Trilinear, Interpolation::Tetrahedral] {
+            let mut values = (0..size.pow(3)).map(|index| function([
+                (index % size) as f32,
+                (index / size % size) as f32,
+                (index / (size * size)) as f32,
+            ])).collect::<Vec<_>>();
+
+            // Measures the error away from the samples that were fitted.
+            let error = |values: &[f32]| -> f32 {
+                let mut error = 0.0;
+
+                for index in 0..7_usize.pow(3) {
+                    let point = [(index % 7) as f32, (index / 7 % 7) as f32, (index / 49) as f32].map(|coordinate| (coordinate + 0.3) * (size - 1) as f32 / 7.0);
+
+                    error += (interpolation.interpolate(size, values, point) - function(point)).powi(2);
+                }
+
+                error
+            };
+
+            let error_before = error(&values);
+            optimize(interpolation, size, &mut values, &samples);
+
+            assert!(error(&values) < error_before, "{:?}", interpolation);
+            assert!(values.iter().all(|value| (0.0..=1.0).contains(value)));
+        }
+    }
+}
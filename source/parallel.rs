@@ -0,0 +1,166 @@
+use lcms2::DisallowCache;
+use lcms2::GlobalContext;
+use lcms2::Transform;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use threadpool::ThreadPool;
+
+use crate::progress::Phase;
+use crate::vector::Scalar;
+use crate::vector::Vector3;
+
+/// The number of chunks each thread gets to take on average. Items are far
+/// from equally expensive, so handing them out in many small chunks keeps
+/// threads that finish early busy.
+const CHUNKS_PER_THREAD: usize = 16;
+
+/// The number of colors transformed at a time.
+const SIZE_CHUNK_TRANSFORM: usize = 4096;
+
+/// A profile transformation of colors made of f32 values, unless otherwise
+/// specified, that can be shared between threads.
+pub type Transformation<T = f32> = Transform<Vector3<T>, Vector3<T>, GlobalContext, DisallowCache>;
+
+/// Applies the given function to each of the given items across the given
+/// number of threads, returning the results in the same order as the items.
+///
+/// The items are split into chunks that the threads take on one at a time as
+/// they become free.
+pub fn map<T, R, F>(count_threads: usize, items: Arc<Vec<T>>, function: F) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    map_with(count_threads, items, None, function).unwrap()
+}
+
+/// Applies the given function to each of the given items across the given
+/// number of threads, as with `map`, counting each item done towards the given
+/// phase. Returns `None` if the phase is cancelled, in which case the threads
+/// stop once they're done with the chunks they're on.
+pub fn map_progress<T, R, F>(count_threads: usize, items: Arc<Vec<T>>, phase: &Phase, function: F) -> Option<Vec<R>>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    map_with(count_threads, items, Some(phase.clone()), function)
+}
+
+fn map_with<T, R, F>(count_threads: usize, items: Arc<Vec<T>>, phase: Option<Phase>, function: F) -> Option<Vec<R>>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    let count_threads = count_threads.clamp(1, items.len().max(1));
+
+    let size_chunk = items.len().div_ceil(count_threads * CHUNKS_PER_THREAD).max(1);
+    let count_chunks = items.len().div_ceil(size_chunk);
+
+    let threadpool = ThreadPool::new(count_threads);
+
+    let arc_next = Arc::new(AtomicUsize::new(0));
+    let arc_function = Arc::new(function);
+    let (sender, receiver) = channel();
+
+    for _ in 0..count_threads {
+        let next = arc_next.clone();
+        let items = items.clone();
+        let function = arc_function.clone();
+        let sender = sender.clone();
+        let phase = phase.clone();
+
+        threadpool.execute(move || loop {
+            let index_chunk = next.fetch_add(1, Ordering::Relaxed);
+
+            if index_chunk >= count_chunks || phase.as_ref().is_some_and(Phase::is_cancelled) {
+                break;
+            }
+
+            let start = index_chunk * size_chunk;
+            let end = (start + size_chunk).min(items.len());
+
+            let result = items[start..end].iter().map(|item| function(item)).collect::<Vec<_>>();
+
+            if let Some(phase) = &phase {
+                phase.advance(result.len() as u64);
+            }
+
+            sender.send((index_chunk, result)).unwrap();
+        });
+    }
+
+    drop(sender);
+
+    let mut results = (0..count_chunks).map(|_| None).collect::<Vec<_>>();
+    for (index_chunk, result) in receiver {
+        results[index_chunk] = Some(result);
+    }
+
+    threadpool.join();
+
+    assert!(threadpool.panic_count() == 0, "A worker thread panicked.");
+
+    // Chunks only ever go missing by cancellation.
+    results.into_iter().collect::<Option<Vec<_>>>().map(|results| results.into_iter().flatten().collect())
+}
+
+/// Transforms the given colors with the given transformation across the given
+/// number of threads.
+pub fn transform<T: Scalar>(count_threads: usize, transformation: &Arc<Transformation<T>>, colors: Vec<Vector3<T>>) -> Vec<Vector3<T>> {
+    transform_with(count_threads, transformation, colors, None).unwrap()
+}
+
+/// Transforms the given colors with the given transformation across the given
+/// number of threads, as with `transform`, counting each color done towards
+/// the given phase. Returns `None` if the phase is cancelled.
+pub fn transform_progress<T: Scalar>(count_threads: usize, transformation: &Arc<Transformation<T>>, colors: Vec<Vector3<T>>, phase: &Phase) -> Option<Vec<Vector3<T>>> {
+    transform_with(count_threads, transformation, colors, Some(phase))
+}
+
+fn transform_with<T: Scalar>(count_threads: usize, transformation: &Arc<Transformation<T>>, colors: Vec<Vector3<T>>, phase: Option<&Phase>) -> Option<Vec<Vector3<T>>> {
+    let chunks = colors.chunks(SIZE_CHUNK_TRANSFORM).map(<[_]>::to_vec).collect::<Vec<_>>();
+    let transformation = transformation.clone();
+    let phase = phase.cloned();
+
+    // Chunks count towards the phase for each of the colors in them, rather
+    // than as one item each.
+    let chunks = map(count_threads, Arc::new(chunks), move |chunk| {
+        if phase.as_ref().is_some_and(Phase::is_cancelled) {
+            return None;
+        }
+
+        let mut chunk = chunk.clone();
+        transformation.transform_in_place(&mut chunk);
+
+        if let Some(phase) = &phase {
+            phase.advance(chunk.len() as u64);
+        }
+
+        Some(chunk)
+    });
+
+    chunks.into_iter().collect::<Option<Vec<_>>>().map(|chunks| chunks.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that results come back in order whatever the number of threads,
+    /// including more threads than items.
+    #[test]
+    fn test_map() {
+        for count in [0, 1, 7, 1000] {
+            let items = Arc::new((0..count).collect::<Vec<usize>>());
+
+            for count_threads in [1, 3, 16] {
+                assert_eq!((0..count).map(|item| 2 * item).collect::<Vec<_>>(), map(count_threads, items.clone(), |item| 2 * item));
+            }
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::io::stderr;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The least time between reports of progress, besides those of phases
+/// finishing.
+const INTERVAL_REPORT: Duration = Duration::from_millis(100);
+
+/// A token that tells everyone holding a copy of it to stop what they're doing.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    /// Returns a new token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token. This only sets a flag, so it's safe to call from a
+    /// signal handler.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A snapshot of the progress of a phase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// The name of the phase.
+    pub name: String,
+    /// The number of items done so far.
+    pub count: u64,
+    /// The number of items to do altogether.
+    pub total: u64,
+    /// The time since the phase started.
+    pub elapsed: Duration,
+    /// Whether the phase has just started.
+    pub started: bool,
+    /// Whether the phase has just finished.
+    pub finished: bool,
+}
+
+impl Report {
+    /// Returns the estimated time until the phase finishes, assuming the items
+    /// left take as long as the items done so far, or `None` if nothing has
+    /// been done yet.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        Some(self.elapsed.mul_f64(self.total.saturating_sub(self.count) as f64 / self.count as f64))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        // Phases of no items aren't counted, only timed.
+        if self.total == 0 && self.finished {
+            return write!(formatter, "\x1B[96m{}\x1B[0m in {}.", self.name, format_duration(self.elapsed));
+        }
+        if self.total == 0 {
+            return write!(formatter, "\x1B[96m{}\x1B[0m", self.name);
+        }
+
+        if self.finished {
+            return write!(formatter, "\x1B[96m{}\x1B[0m: {} in {}.", self.name, self.total, format_duration(self.elapsed));
+        }
+
+        write!(formatter, "\x1B[96m{}\x1B[0m: {}/{}", self.name, self.count, self.total)?;
+
+        if let Some(percentage) = (100 * self.count).checked_div(self.total) {
+            write!(formatter, " ({}%)", percentage)?;
+        }
+
+        match self.eta() {
+            Some(eta) => write!(formatter, ", ETA {}", format_duration(eta)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A receiver of progress reports, which is handed a report of every phase in
+/// progress at once, including any that have just started or finished.
+pub type Callback = dyn Fn(&[Report]) + Send + Sync;
+
+/// Tracks the progress of the phases of the program, reporting on them to a
+/// callback every so often, and carries the token that cancels them.
+#[derive(Clone)]
+pub struct Progress {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    callback: Option<Box<Callback>>,
+    cancellation: Cancellation,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// The phases in progress.
+    phases: Vec<PhaseState>,
+    /// The number of phases ever started, which gives each its ID.
+    count_phases: usize,
+    /// When progress was last reported.
+    reported: Option<Instant>,
+}
+
+struct PhaseState {
+    id: usize,
+    name: String,
+    total: u64,
+    start: Instant,
+    count: Arc<AtomicU64>,
+}
+
+/// A phase of the program, made up of some number of items. Copies of a phase
+/// can be handed to workers, which each report the items they get done.
+#[derive(Clone)]
+pub struct Phase {
+    progress: Progress,
+    id: usize,
+    count: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Returns a tracker that reports progress to the given callback, and whose
+    /// phases are cancelled with the given token.
+    pub fn new(callback: impl Fn(&[Report]) + Send + Sync + 'static, cancellation: Cancellation) -> Self {
+        Self::with(Some(Box::new(callback)), cancellation)
+    }
+
+    /// Returns a tracker that doesn't report progress anywhere, and whose
+    /// phases are cancelled with the given token.
+    pub fn silent(cancellation: Cancellation) -> Self {
+        Self::with(None, cancellation)
+    }
+
+    /// Returns a tracker that reports progress to the standard error stream,
+    /// and whose phases are cancelled with the given token.
+    ///
+    /// On a terminal, the phases in progress are shown on a status line that
+    /// keeps being redrawn. Otherwise, phases are only shown as they start and
+    /// as they finish.
+    pub fn stderr(cancellation: Cancellation) -> Self {
+        let terminal = stderr().is_terminal();
+
+        Self::new(move |reports| {
+            let mut stderr = stderr().lock();
+
+            if terminal {
+                let _ = write!(stderr, "\r\x1B[2K");
+            }
+
+            for report in reports.iter().filter(|report| report.finished || (report.started && !terminal)) {
+                let _ = writeln!(stderr, "{}", report);
+            }
+
+            if terminal {
+                let status = reports.iter().filter(|report| !report.finished).map(Report::to_string).collect::<Vec<_>>();
+
+                let _ = write!(stderr, "{}", status.join(" | "));
+            }
+
+            let _ = stderr.flush();
+        }, cancellation)
+    }
+
+    fn with(callback: Option<Box<Callback>>, cancellation: Cancellation) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                callback,
+                cancellation,
+                state: Mutex::new(State {
+                    phases: Vec::new(),
+                    count_phases: 0,
+                    reported: None,
+                }),
+            }),
+        }
+    }
+
+    /// Starts a phase with the given name made up of the given number of
+    /// items. A phase of no items is only timed, for work that can't be
+    /// counted as it goes.
+    pub fn phase(&self, name: impl Into<String>, total: u64) -> Phase {
+        let count = Arc::new(AtomicU64::new(0));
+
+        let mut state = self.inner.state.lock().unwrap();
+        let id = state.count_phases;
+        state.count_phases += 1;
+        state.phases.push(PhaseState {
+            id,
+            name: name.into(),
+            total,
+            start: Instant::now(),
+            count: count.clone(),
+        });
+        drop(state);
+
+        self.report(Some(id), None);
+
+        Phase {
+            progress: self.clone(),
+            id,
+            count,
+        }
+    }
+
+    /// Returns whether the phases have been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancellation.is_cancelled()
+    }
+
+    /// Reports on the phases in progress, unless the last report was too
+    /// recent, on the phase with the first given ID as having started, and on
+    /// the phase with the second given ID as having finished.
+    fn report(&self, started: Option<usize>, finished: Option<usize>) {
+        if self.inner.callback.is_none() && finished.is_none() {
+            return;
+        }
+
+        // Phases starting or finishing are always reported, while other
+        // reports that come too soon are skipped anyway, so there's no point
+        // waiting for another thread to finish one.
+        let forced = started.is_some() || finished.is_some();
+
+        let mut state = if forced {
+            self.inner.state.lock().unwrap()
+        }
+        else {
+            match self.inner.state.try_lock() {
+                Ok(state) => state,
+                Err(_) => return,
+            }
+        };
+
+        let now = Instant::now();
+
+        if !forced && state.reported.is_some_and(|reported| now - reported < INTERVAL_REPORT) {
+            return;
+        }
+
+        let reports = state.phases.iter().map(|phase| Report {
+            name: phase.name.clone(),
+            count: phase.count.load(Ordering::Relaxed).min(phase.total),
+            total: phase.total,
+            elapsed: now - phase.start,
+            started: Some(phase.id) == started,
+            finished: Some(phase.id) == finished,
+        }).collect::<Vec<_>>();
+
+        state.phases.retain(|phase| Some(phase.id) != finished);
+        state.reported = Some(now);
+
+        if let Some(callback) = &self.inner.callback {
+            callback(&reports);
+        }
+    }
+}
+
+impl Phase {
+    /// Records that the given number of the phase's items got done.
+    pub fn advance(&self, count: u64) {
+        self.count.fetch_add(count, Ordering::Relaxed);
+
+        self.progress.report(None, None);
+    }
+
+    /// Finishes the phase, unless it's been cancelled, in which case it's left
+    /// unfinished.
+    pub fn finish(self) {
+        if !self.is_cancelled() {
+            self.progress.report(None, Some(self.id));
+        }
+    }
+
+    /// Returns whether the phase has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+}
+
+/// Formats the given duration as hours, minutes and seconds.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+    else if seconds >= 60 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+    else {
+        format!("{:.1}s", duration.as_secs_f32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that phases report their counts, and are reported as started and
+    /// as finished exactly once.
+    #[test]
+    fn test_progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+
+        let progress = {
+            let reports = reports.clone();
+
+            Progress::new(move |snapshot| reports.lock().unwrap().extend_from_slice(snapshot), Cancellation::new())
+        };
+
+        let phase = progress.phase("Phase", 10);
+        phase.advance(4);
+        phase.clone().advance(6);
+        phase.finish();
+
+        let reports = reports.lock().unwrap();
+        let started = reports.iter().filter(|report| report.started).collect::<Vec<_>>();
+        let finished = reports.iter().filter(|report| report.finished).collect::<Vec<_>>();
+
+        assert_eq!(1, started.len());
+        assert_eq!(("Phase", 0), (started[0].name.as_str(), started[0].count));
+        assert_eq!(1, finished.len());
+        assert_eq!(("Phase", 10, 10), (finished[0].name.as_str(), finished[0].count, finished[0].total));
+        assert!(reports.windows(2).all(|pair| pair[0].count <= pair[1].count));
+    }
+
+    /// Tests the estimated time left.
+    #[test]
+    fn test_eta() {
+        let mut report = Report {
+            name: String::from("Phase"),
+            count: 0,
+            total: 100,
+            elapsed: Duration::from_secs(10),
+            started: false,
+            finished: false,
+        };
+
+        assert_eq!(None, report.eta());
+
+        report.count = 25;
+        assert_eq!(Some(Duration::from_secs(30)), report.eta());
+    }
+
+    /// Tests that reports show counts, except for phases of no items.
+    #[test]
+    fn test_display() {
+        let mut report = Report {
+            name: String::from("Phase"),
+            count: 25,
+            total: 100,
+            elapsed: Duration::from_secs(10),
+            started: false,
+            finished: false,
+        };
+
+        assert_eq!("\x1B[96mPhase\x1B[0m: 25/100 (25%), ETA 30.0s", report.to_string());
+
+        report.finished = true;
+        assert_eq!("\x1B[96mPhase\x1B[0m: 100 in 10.0s.", report.to_string());
+
+        report.total = 0;
+        assert_eq!("\x1B[96mPhase\x1B[0m in 10.0s.", report.to_string());
+
+        report.finished = false;
+        assert_eq!("\x1B[96mPhase\x1B[0m", report.to_string());
+    }
+
+    /// Tests that cancelling a token cancels every copy of it.
+    #[test]
+    fn test_cancellation() {
+        let cancellation = Cancellation::new();
+        let progress = Progress::silent(cancellation.clone());
+        let phase = progress.phase("Phase", 1);
+
+        assert!(!phase.is_cancelled());
+
+        cancellation.cancel();
+
+        assert!(progress.is_cancelled());
+        assert!(phase.is_cancelled());
+    }
+}
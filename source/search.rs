@@ -0,0 +1,508 @@
+use rstar::RTree;
+use std::sync::Arc;
+
+use crate::mapping::Mapping;
+use crate::parallel;
+use crate::secondaries::Secondary;
+use crate::vector::Vector3;
+
+/// The number of secondary colors each cell of a grid aims to hold.
+const COUNT_PER_CELL: usize = 32;
+
+/// The largest number of cells along each axis of a grid.
+const COUNT_CELLS_MAX: usize = 1024;
+
+/// The largest number of entries along each axis of a lookup table.
+const COUNT_ENTRIES_MAX: usize = 64;
+
+/// A structure for finding the secondary colors nearest to a given color.
+pub trait Search: Send + Sync {
+    /// Returns the indices of the given number of secondary colors nearest to
+    /// the given point under the given mapping, nearest first. The point must
+    /// be in the mapping's space.
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64>;
+
+    /// Returns the index of the secondary color nearest to the given point
+    /// under the given mapping. The point must be in the mapping's space.
+    fn nearest(&self, mapping: &Mapping, point: Vector3) -> u64 {
+        self.nearest_k(mapping, point, 1)[0]
+    }
+}
+
+/// A kind of structure to search secondary colors held in memory with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// An R-tree.
+    RTree,
+    /// A uniform grid of cells over the mapping's space.
+    Grid,
+    /// A k-d tree.
+    KdTree,
+    /// A lookup table of the secondary color nearest to each of its entries,
+    /// over a uniform grid.
+    Table,
+}
+
+/// Returns a structure of the given kind to search the given secondary colors
+/// with under the given mapping. There must be at least one secondary color.
+/// Any precomputation is spread across the given number of threads.
+pub fn build(backend: Backend, mapping: &Mapping, secondaries: Vec<Secondary>, count_threads: usize) -> Box<dyn Search> {
+    match backend {
+        Backend::RTree => Box::new(RTree::bulk_load(secondaries)),
+        Backend::Grid => Box::new(Voxels::new(secondaries)),
+        Backend::KdTree => Box::new(KdTree::new(secondaries)),
+        Backend::Table => Box::new(Table::new(mapping, secondaries, count_threads)),
+    }
+}
+
+impl Search for RTree<Secondary> {
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64> {
+        mapping.nearest_k(self, point, count).into_iter().map(|secondary| secondary.data).collect()
+    }
+
+    fn nearest(&self, mapping: &Mapping, point: Vector3) -> u64 {
+        mapping.nearest(self, point).unwrap().data
+    }
+}
+
+/// A uniform grid of cubic cells over the bounds of some points.
+pub struct Grid {
+    pub minimum: Vector3,
+    pub size_cell: Vector3,
+    pub count_cells: usize,
+}
+
+impl Grid {
+    /// Returns a grid over the given bounds with about the right number of
+    /// cells for the given number of points.
+    pub fn new(minimum: Vector3, maximum: Vector3, count: usize) -> Self {
+        let count_cells = ((count / COUNT_PER_CELL) as f64).cbrt().ceil().clamp(1.0, COUNT_CELLS_MAX as f64) as usize;
+
+        Self::with_cells(minimum, maximum, count_cells)
+    }
+
+    /// Returns a grid over the given bounds with the given number of cells
+    /// along each axis.
+    fn with_cells(minimum: Vector3, maximum: Vector3, count_cells: usize) -> Self {
+        let mut size_cell = (maximum - minimum) / count_cells as f32;
+        for axis in 0..3 {
+            if size_cell[axis].is_nan() || size_cell[axis] <= 0.0 {
+                size_cell[axis] = 1.0;
+            }
+        }
+
+        Self {
+            minimum,
+            size_cell,
+            count_cells,
+        }
+    }
+
+    /// Returns the grid coordinates of the cell containing the given point, or
+    /// the cell nearest to it if the point lies outside the grid.
+    pub fn coordinates(&self, point: Vector3) -> [usize; 3] {
+        let mut coordinates = [0; 3];
+
+        for axis in 0..3 {
+            let coordinate = ((point[axis] - self.minimum[axis]) / self.size_cell[axis]).floor();
+
+            coordinates[axis] = coordinate.clamp(0.0, (self.count_cells - 1) as f32) as usize;
+        }
+
+        coordinates
+    }
+
+    /// Returns the index of the cell with the given grid coordinates.
+    pub fn cell(&self, coordinates: [usize; 3]) -> usize {
+        (coordinates[2] * self.count_cells + coordinates[1]) * self.count_cells + coordinates[0]
+    }
+
+    /// Returns the center of the cell with the given grid coordinates.
+    fn center(&self, coordinates: [usize; 3]) -> Vector3 {
+        let mut center = self.minimum;
+
+        for axis in 0..3 {
+            center[axis] += (coordinates[axis] as f32 + 0.5) * self.size_cell[axis];
+        }
+
+        center
+    }
+
+    /// Returns the squared Euclidean distance from the given point to the cell
+    /// with the given grid coordinates.
+    fn distance_squared(&self, point: Vector3, coordinates: [usize; 3]) -> f32 {
+        let mut distance = 0.0;
+
+        for axis in 0..3 {
+            let start = self.minimum[axis] + coordinates[axis] as f32 * self.size_cell[axis];
+            let end = start + self.size_cell[axis];
+
+            distance += (start - point[axis]).max(point[axis] - end).max(0.0).powi(2);
+        }
+
+        distance
+    }
+
+    /// Returns a lower bound of the Euclidean distance from the given point to
+    /// any cell farther than the given Chebyshev distance from the given cell,
+    /// or `None` if there are no such cells.
+    fn bound(&self, point: Vector3, center: [usize; 3], radius: usize) -> Option<f32> {
+        let mut bound = None::<f32>;
+
+        for axis in 0..3 {
+            let start = |coordinate: usize| self.minimum[axis] + coordinate as f32 * self.size_cell[axis];
+
+            if center[axis] + radius + 1 < self.count_cells {
+                let distance = (start(center[axis] + radius + 1) - point[axis]).max(0.0);
+                bound = Some(bound.map_or(distance, |bound| bound.min(distance)));
+            }
+
+            if center[axis] > radius {
+                let distance = (point[axis] - start(center[axis] - radius)).max(0.0);
+                bound = Some(bound.map_or(distance, |bound| bound.min(distance)));
+            }
+        }
+
+        bound
+    }
+
+    /// Returns the indices of the given number of points nearest to the given
+    /// point under the given mapping, nearest first, given a function that
+    /// returns the coordinates and indices of the points in the cell with the
+    /// given index.
+    pub fn nearest_k<I: Iterator<Item = (Vector3, u64)>>(&self, mapping: &Mapping, point: Vector3, count: usize, records: impl Fn(usize) -> I) -> Vec<u64> {
+        self.nearest_k_from(mapping, point, count, Vec::with_capacity(count + 1), records)
+    }
+
+    /// Returns the indices of the given number of points nearest to the given
+    /// point as `nearest_k` does, starting from the given squared distances and
+    /// indices of the nearest points known so far, nearest first, which spare
+    /// scanning any cell farther away than they are. Points known so far are
+    /// only found again if they are strictly nearer than the farthest of them.
+    fn nearest_k_from<I: Iterator<Item = (Vector3, u64)>>(&self, mapping: &Mapping, point: Vector3, count: usize, mut nearest: Vec<(f32, u64)>, records: impl Fn(usize) -> I) -> Vec<u64> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let center = self.coordinates(point);
+
+        // Scan shells of cells of increasing Chebyshev distance around the
+        // cell nearest to the point until nothing farther out can be nearer.
+        // Euclidean distances in the mapping's space never exceed the
+        // mapping's distances, so they bound what's left to scan.
+        for radius in 0.. {
+            let range = |axis: usize| center[axis].saturating_sub(radius)..=(center[axis] + radius).min(self.count_cells - 1);
+
+            let mut scan = |coordinates: [usize; 3]| {
+                // Skip cells that can't possibly hold anything nearer.
+                if nearest.len() == count && self.distance_squared(point, coordinates) >= nearest[count - 1].0 {
+                    return;
+                }
+
+                for (candidate, index) in records(self.cell(coordinates)) {
+                    insert(&mut nearest, count, mapping.distance_squared(candidate, point), index);
+                }
+            };
+
+            for z in range(2) {
+                for y in range(1) {
+                    // Rows on the shell's faces are scanned whole, the rest
+                    // only at their ends.
+                    if z.abs_diff(center[2]) == radius || y.abs_diff(center[1]) == radius {
+                        for x in range(0) {
+                            scan([x, y, z]);
+                        }
+                    }
+                    else {
+                        if center[0] >= radius {
+                            scan([center[0] - radius, y, z]);
+                        }
+                        if center[0] + radius < self.count_cells {
+                            scan([center[0] + radius, y, z]);
+                        }
+                    }
+                }
+            }
+
+            match self.bound(point, center, radius) {
+                Some(bound) if nearest.len() < count || bound.powi(2) < nearest[count - 1].0 => (),
+                _ => break,
+            }
+        }
+
+        nearest.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+/// Secondary colors held in memory, bucketed into the cells of a uniform grid
+/// over the mapping's space, so that a search only ever touches the cells
+/// around the point being searched for. For dense secondary colors, this is
+/// much quicker to search than an R-tree.
+pub struct Voxels {
+    grid: Grid,
+    /// The index of the first secondary color of each cell, followed by the
+    /// number of secondary colors.
+    offsets: Vec<usize>,
+    secondaries: Vec<Secondary>,
+}
+
+impl Voxels {
+    /// Buckets the given secondary colors into a grid.
+    pub fn new(mut secondaries: Vec<Secondary>) -> Self {
+        let (minimum, maximum) = bounds(&secondaries);
+
+        let grid = Grid::new(minimum, maximum, secondaries.len());
+
+        // Sorting by cell puts each cell's secondary colors next to each other.
+        secondaries.sort_by_cached_key(|secondary| grid.cell(grid.coordinates(*secondary.geom())));
+
+        let mut offsets = vec![0; grid.count_cells.pow(3) + 1];
+
+        for secondary in secondaries.iter() {
+            offsets[grid.cell(grid.coordinates(*secondary.geom())) + 1] += 1;
+        }
+        for index_cell in 0..grid.count_cells.pow(3) {
+            offsets[index_cell + 1] += offsets[index_cell];
+        }
+
+        Self {
+            grid,
+            offsets,
+            secondaries,
+        }
+    }
+
+    /// Returns the coordinates and indices of the secondary colors in the cell
+    /// with the given index.
+    fn records(&self, cell: usize) -> impl Iterator<Item = (Vector3, u64)> + '_ {
+        self.secondaries[self.offsets[cell]..self.offsets[cell + 1]].iter().map(|secondary| (*secondary.geom(), secondary.data))
+    }
+
+    /// Returns the coordinates of the secondary colors in the cell with the
+    /// given index, along with their positions among all of them.
+    fn positions(&self, cell: usize) -> impl Iterator<Item = (Vector3, u64)> + '_ {
+        (self.offsets[cell]..self.offsets[cell + 1]).map(|position| (*self.secondaries[position].geom(), position as u64))
+    }
+}
+
+impl Search for Voxels {
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64> {
+        self.grid.nearest_k(mapping, point, count, |cell| self.records(cell))
+    }
+}
+
+/// Secondary colors held in memory as a balanced k-d tree, laid out implicitly
+/// with each subtree's root in the middle of its range and the axes split on in
+/// turn.
+pub struct KdTree {
+    secondaries: Vec<Secondary>,
+}
+
+impl KdTree {
+    /// Arranges the given secondary colors into a k-d tree.
+    pub fn new(mut secondaries: Vec<Secondary>) -> Self {
+        /// Arranges the given secondary colors into a subtree of the given
+        /// depth.
+        fn arrange(secondaries: &mut [Secondary], depth: usize) {
+            if secondaries.len() <= 1 {
+                return;
+            }
+
+            let middle = secondaries.len() / 2;
+            let axis = depth % 3;
+
+            secondaries.select_nth_unstable_by(middle, |a, b| a.geom()[axis].total_cmp(&b.geom()[axis]));
+
+            let (below, above) = secondaries.split_at_mut(middle);
+            arrange(below, depth + 1);
+            arrange(&mut above[1..], depth + 1);
+        }
+
+        arrange(&mut secondaries, 0);
+
+        Self {
+            secondaries,
+        }
+    }
+}
+
+impl Search for KdTree {
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64> {
+        /// Searches the given subtree of the given depth, whose bounds are the
+        /// given offsets along each axis and the given squared Euclidean
+        /// distance away from the point, which never exceeds the mapping's.
+        #[allow(clippy::too_many_arguments)]
+        fn visit(secondaries: &[Secondary], depth: usize, offsets: &mut [f32; 3], distance_bounds: f32, mapping: &Mapping, point: Vector3, count: usize, nearest: &mut Vec<(f32, u64)>) {
+            if secondaries.is_empty() || (nearest.len() == count && distance_bounds >= nearest[count - 1].0) {
+                return;
+            }
+
+            let middle = secondaries.len() / 2;
+            let axis = depth % 3;
+            let secondary = &secondaries[middle];
+
+            insert(nearest, count, mapping.distance_squared(*secondary.geom(), point), secondary.data);
+
+            // Search the side of the split the point is on first, and the other
+            // only if it could hold anything nearer.
+            let difference = point[axis] - secondary.geom()[axis];
+            let (near, far) = if difference < 0.0 {
+                (&secondaries[..middle], &secondaries[middle + 1..])
+            }
+            else {
+                (&secondaries[middle + 1..], &secondaries[..middle])
+            };
+
+            visit(near, depth + 1, offsets, distance_bounds, mapping, point, count, nearest);
+
+            let offset = offsets[axis];
+            offsets[axis] = difference;
+            visit(far, depth + 1, offsets, distance_bounds - offset.powi(2) + difference.powi(2), mapping, point, count, nearest);
+            offsets[axis] = offset;
+        }
+
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let mut nearest = Vec::with_capacity(count + 1);
+
+        visit(&self.secondaries, 0, &mut [0.0; 3], 0.0, mapping, point, count, &mut nearest);
+
+        nearest.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+/// A lookup table of the secondary color nearest to the center of each cell of
+/// a uniform grid over the mapping's space, over secondary colors bucketed as
+/// in `Voxels`. Finding the secondary color nearest to a point starts from the
+/// one nearest to the center of the cell it lies in, which is usually near
+/// enough that only the cells right around the point are left to scan for any
+/// nearer one.
+///
+/// Searches for several secondary colors scan the buckets as `Voxels` does.
+pub struct Table {
+    grid: Grid,
+    /// The position among the bucketed secondary colors of the one nearest to
+    /// the center of each cell.
+    nearest: Vec<usize>,
+    voxels: Arc<Voxels>,
+}
+
+impl Table {
+    /// Builds a lookup table for the given mapping of the given secondary
+    /// colors across the given number of threads.
+    pub fn new(mapping: &Mapping, secondaries: Vec<Secondary>, count_threads: usize) -> Self {
+        let (minimum, maximum) = bounds(&secondaries);
+
+        // About as many entries as secondary colors, within reason.
+        let count_entries = (secondaries.len() as f64).cbrt().ceil().clamp(1.0, COUNT_ENTRIES_MAX as f64) as usize;
+
+        let grid = Grid::with_cells(minimum, maximum, count_entries);
+
+        let centers = (0..count_entries.pow(3)).map(|index| grid.center([
+            index % count_entries,
+            index / count_entries % count_entries,
+            index / (count_entries * count_entries),
+        ])).collect::<Vec<_>>();
+
+        let voxels = Arc::new(Voxels::new(secondaries));
+        let mapping = *mapping;
+
+        let nearest = {
+            let voxels = voxels.clone();
+
+            parallel::map(count_threads, Arc::new(centers), move |center| {
+                voxels.grid.nearest_k(&mapping, *center, 1, |cell| voxels.positions(cell))[0] as usize
+            })
+        };
+
+        Self {
+            grid,
+            nearest,
+            voxels,
+        }
+    }
+}
+
+impl Search for Table {
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64> {
+        self.voxels.nearest_k(mapping, point, count)
+    }
+
+    fn nearest(&self, mapping: &Mapping, point: Vector3) -> u64 {
+        let secondary = &self.voxels.secondaries[self.nearest[self.grid.cell(self.grid.coordinates(point))]];
+        let nearest = vec![(mapping.distance_squared(*secondary.geom(), point), secondary.data)];
+
+        self.voxels.grid.nearest_k_from(mapping, point, 1, nearest, |cell| self.voxels.records(cell))[0]
+    }
+}
+
+/// Returns the minimum and maximum coordinates of the given secondary colors.
+fn bounds(secondaries: &[Secondary]) -> (Vector3, Vector3) {
+    let mut minimum = Vector3([f32::INFINITY; 3]);
+    let mut maximum = Vector3([f32::NEG_INFINITY; 3]);
+
+    for secondary in secondaries.iter() {
+        for axis in 0..3 {
+            minimum[axis] = minimum[axis].min(secondary.geom()[axis]);
+            maximum[axis] = maximum[axis].max(secondary.geom()[axis]);
+        }
+    }
+
+    (minimum, maximum)
+}
+
+/// Inserts the point with the given squared distance and index into the given
+/// list of at most the given number of the nearest points found so far, if it's
+/// nearer than any of them.
+fn insert(nearest: &mut Vec<(f32, u64)>, count: usize, distance: f32, index: u64) {
+    if nearest.len() < count || distance < nearest[count - 1].0 {
+        let position = nearest.partition_point(|(distance_other, _)| *distance_other <= distance);
+        nearest.insert(position, (distance, index));
+        nearest.truncate(count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::mapping::WEIGHTS_HUE;
+    use crate::secondaries;
+    use crate::secondaries::Lattice;
+    use crate::testing;
+
+    /// Tests that every backend agrees with an exhaustive search, including for
+    /// points outside of the secondary colors' bounds.
+    #[test]
+    fn test_backends() {
+        let inks = testing::inks(3, 2.0);
+        let lattice = Lattice::new(20, 3).unwrap();
+
+        for mapping in [Mapping::Colorimetric, Mapping::Weighted(WEIGHTS_HUE)] {
+            let secondaries = secondaries::enumerate(&inks, &mapping, lattice, 2);
+
+            for color in [Vector3([0.5, 0.5, 0.5]), Vector3([0.1, 0.05, 0.3]), Vector3([2.0, -1.0, 0.0])] {
+                let point = mapping.point(color, inks.white);
+
+                let distances = secondaries.iter().map(|secondary| (secondary.data, mapping.distance_squared(*secondary.geom(), point))).collect::<HashMap<_, _>>();
+                let distance = |index: &u64| distances[index];
+
+                let mut reference = secondaries.iter().map(|secondary| secondary.data).collect::<Vec<_>>();
+                reference.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+
+                for backend in [Backend::RTree, Backend::Grid, Backend::KdTree, Backend::Table] {
+                    let search = build(backend, &mapping, secondaries.clone(), 2);
+
+                    let result = search.nearest_k(&mapping, point, 3);
+
+                    assert_eq!(reference[..3].iter().map(distance).collect::<Vec<_>>(), result.iter().map(distance).collect::<Vec<_>>(), "{:?}", backend);
+                    assert_eq!(distance(&reference[0]), distance(&search.nearest(&mapping, point)), "{:?}", backend);
+                }
+            }
+        }
+    }
+}
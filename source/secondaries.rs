@@ -0,0 +1,510 @@
+use rstar::primitives::GeomWithData;
+use rstar::RTree;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::inks::Inks;
+use crate::mapping::Mapping;
+use crate::parallel;
+use crate::progress::Phase;
+use crate::progress::Progress;
+use crate::vector::Vector3;
+
+/// The number of secondary colors the coarsest level of adaptive sampling aims
+/// to start from.
+const TARGET_ADAPTIVE_COARSE: usize = 4096;
+
+/// The radius, in steps of the finer grid, of the neighborhood sampled around
+/// each grid point selected by adaptive sampling.
+const RADIUS_ADAPTIVE: usize = 1;
+
+/// The number of grid points nearest to each point that adaptive sampling
+/// refines. Refining more than just the nearest one keeps sampling from
+/// committing too early to the wrong one of several similar mixtures.
+const COUNT_NEAREST_ADAPTIVE: usize = 4;
+
+/// A secondary color, positioned at its coordinates in the mapping's space,
+/// carrying only the index of its ink fractions on the lattice it was sampled
+/// from. Its fractions and its XYZ color are recomputed on demand.
+pub type Secondary = GeomWithData<Vector3, u64>;
+
+/// A uniform sampling of ink fractions, whose points are identified by compact
+/// indices: the number whose digits, in base resolution, are the points'
+/// coordinates, with the first ink as the least significant digit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lattice {
+    /// The number of samples per ink, including 0 and 1.
+    pub resolution: usize,
+    /// The number of inks.
+    pub count_primaries: usize,
+}
+
+impl Lattice {
+    /// Returns the lattice with the given resolution for the given number of
+    /// inks, or `None` if the lattice has too many points to index.
+    pub fn new(resolution: usize, count_primaries: usize) -> Option<Self> {
+        (resolution as u64).checked_pow(count_primaries as u32)?;
+
+        Some(Self {
+            resolution,
+            count_primaries,
+        })
+    }
+
+    /// Returns the largest resolution whose lattice for the given number of
+    /// inks can be indexed.
+    pub fn resolution_max(count_primaries: usize) -> usize {
+        let mut resolution = (u64::MAX as f64).powf(1.0 / count_primaries as f64) as u64;
+
+        while resolution.checked_pow(count_primaries as u32).is_none() {
+            resolution -= 1;
+        }
+
+        resolution as usize
+    }
+
+    /// Returns the ink fractions of the point with the given index.
+    pub fn fractions(&self, index: u64) -> Vec<f32> {
+        self.digits(index).into_iter().map(|digit| digit as f32 / (self.resolution - 1) as f32).collect()
+    }
+
+    /// Returns the coordinates of the point with the given index.
+    pub fn digits(&self, mut index: u64) -> Vec<usize> {
+        let mut digits = Vec::with_capacity(self.count_primaries);
+
+        for _ in 0..self.count_primaries {
+            digits.push((index % self.resolution as u64) as usize);
+            index /= self.resolution as u64;
+        }
+
+        digits
+    }
+
+    /// Returns the index of the point with the given coordinates.
+    pub fn index(&self, digits: &[usize]) -> u64 {
+        digits.iter().rev().fold(0, |index, digit| index * self.resolution as u64 + *digit as u64)
+    }
+}
+
+/// The largest number of steps of the sampling grid that counting mixtures will
+/// consider, beyond which there would be far too many mixtures anyway.
+pub const STEPS_MAX: usize = 1 << 24;
+
+/// Returns the smallest resolution of a uniform sampling of ink fractions that
+/// yields at least the given target number of mixtures of the given number of
+/// inks that respect the given ink limit, or `None` if there is no such
+/// resolution whose lattice can be indexed.
+pub fn resolution(target: usize, count_primaries: usize, limit: f32) -> Option<usize> {
+    let target = target as u128;
+
+    // With no ink allowed, only the bare paper is left, at any resolution.
+    if limit <= 0.0 {
+        return Some(2);
+    }
+
+    let resolution_max = Lattice::resolution_max(count_primaries);
+
+    // Returns whether the given resolution meets the target, or `None` if the
+    // resolution is too large to even count.
+    let meets = |resolution: usize| -> Option<bool> {
+        if resolution > resolution_max || (count_primaries > 1 && steps(resolution, count_primaries, limit) > STEPS_MAX) {
+            return None;
+        }
+
+        Some(count(resolution, count_primaries, limit) >= target)
+    };
+
+    // No mixture can have more ink than all of the inks at full coverage, so
+    // the unconstrained resolution is where to start searching.
+    let mut low = ((target as f64).powf(1.0 / count_primaries as f64).ceil() as usize).max(2);
+    if meets(low)? {
+        return Some(low);
+    }
+
+    let mut high = low;
+    while !meets(high)? {
+        if high == resolution_max {
+            return None;
+        }
+
+        low = high;
+        high = high.saturating_mul(2).min(resolution_max);
+    }
+
+    // The count is nondecreasing with the resolution, so bisect the rest.
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+
+        if meets(middle)? {
+            high = middle;
+        }
+        else {
+            low = middle;
+        }
+    }
+
+    Some(high)
+}
+
+/// Returns the number of mixtures of the given number of inks that respect the
+/// given ink limit when fractions are sampled uniformly at the given
+/// resolution.
+pub fn count(resolution: usize, count_primaries: usize, limit: f32) -> u128 {
+    let steps = steps(resolution, count_primaries, limit);
+
+    if count_primaries == 1 {
+        return steps.min(resolution - 1) as u128 + 1;
+    }
+
+    // Count the ways to spend at most the given number of steps, one primary
+    // at a time, each of which can spend between 0 and resolution - 1 steps.
+    let mut ways = vec![0_u128; steps + 1];
+    ways[0] = 1;
+
+    for _ in 0..count_primaries {
+        let mut total = 0_u128;
+        let mut ways_next = vec![0_u128; steps + 1];
+
+        for spent in 0..=steps {
+            total = total.saturating_add(ways[spent]);
+            if spent >= resolution {
+                total -= ways[spent - resolution];
+            }
+
+            ways_next[spent] = total;
+        }
+
+        ways = ways_next;
+    }
+
+    ways.into_iter().fold(0, u128::saturating_add)
+}
+
+/// Returns the total number of steps of the sampling grid at the given
+/// resolution that the given number of inks may spend without violating the
+/// given ink limit.
+pub fn steps(resolution: usize, count_primaries: usize, limit: f32) -> usize {
+    let steps_max = count_primaries * (resolution - 1);
+
+    // A little slack keeps limits that land exactly on the grid, like whole
+    // numbers, from losing a step to rounding.
+    let steps = limit as f64 * (resolution - 1) as f64 + 1e-6;
+
+    if steps >= steps_max as f64 {
+        steps_max
+    }
+    else {
+        steps.floor() as usize
+    }
+}
+
+/// Mixes the given inks together at every point of the given lattice that
+/// respects the ink limit, across the given number of threads.
+pub fn enumerate(inks: &Inks, mapping: &Mapping, lattice: Lattice, count_threads: usize) -> Vec<Secondary> {
+    let mut secondaries = Vec::with_capacity(count(lattice.resolution, lattice.count_primaries, inks.limit) as usize);
+
+    enumerate_with(inks, mapping, lattice, count_threads, None, &mut |point, index| {
+        secondaries.push(GeomWithData::new(point, index));
+    });
+
+    secondaries
+}
+
+/// Mixes the given inks together at every point of the given lattice that
+/// respects the ink limit, across the given number of threads, handing the
+/// coordinates of each mixture in the mapping's space and its index to the
+/// given function in order of their indices' last ink. Each mixture counts
+/// towards the given phase, if any, and mixing stops short if it's cancelled.
+///
+/// The fractions of the last ink are shared out between the threads a few at a
+/// time, so that only the mixtures of those few are ever held in memory.
+pub fn enumerate_with(inks: &Inks, mapping: &Mapping, lattice: Lattice, count_threads: usize, phase: Option<&Phase>, function: &mut impl FnMut(Vector3, u64)) {
+    let steps = steps(lattice.resolution, lattice.count_primaries, inks.limit);
+
+    let index_primary = lattice.count_primaries - 1;
+    let place = (lattice.resolution as u64).pow(index_primary as u32);
+    let steps_last = (0..=steps.min(lattice.resolution - 1)).collect::<Vec<_>>();
+
+    let inks = Arc::new(inks.clone());
+    let mapping = *mapping;
+
+    for steps_batch in steps_last.chunks(count_threads.max(1) * 4) {
+        let inks = inks.clone();
+        let phase = phase.cloned();
+
+        let batches = parallel::map(count_threads, Arc::new(steps_batch.to_vec()), move |step| {
+            if phase.as_ref().is_some_and(Phase::is_cancelled) {
+                return None;
+            }
+
+            let fraction = *step as f32 / (lattice.resolution - 1) as f32;
+            let primary = inks.primaries[index_primary];
+
+            let mixture = inks.white * ((fraction * primary + (1.0 - fraction) * inks.white) / inks.white);
+
+            let mut batch = Vec::new();
+            walk(&inks, &mapping, lattice, steps - step, index_primary, mixture, *step as u64 * place, &mut |point, index| {
+                batch.push((point, index));
+            });
+
+            if let Some(phase) = &phase {
+                phase.advance(batch.len() as u64);
+            }
+
+            Some(batch)
+        });
+
+        let Some(batches) = batches.into_iter().collect::<Option<Vec<_>>>() else {
+            return;
+        };
+
+        for (point, index) in batches.into_iter().flatten() {
+            function(point, index);
+        }
+    }
+}
+
+/// Mixes every fraction of the first given number of inks that can be afforded
+/// with the given number of steps into the given mixture of the remaining inks,
+/// whose coordinates are already accounted for in the given partial index.
+///
+/// Only mixtures that respect the ink limit are ever visited, rather than
+/// visiting them all and discarding the ones that don't.
+#[allow(clippy::too_many_arguments)]
+fn walk(
+    inks: &Inks,
+    mapping: &Mapping,
+    lattice: Lattice,
+    steps: usize,
+    count: usize,
+    secondary: Vector3,
+    index: u64,
+    function: &mut impl FnMut(Vector3, u64),
+) {
+    if count == 0 {
+        function(mapping.point(secondary, inks.white), index);
+        return;
+    }
+
+    let index_primary = count - 1;
+    let primary = inks.primaries[index_primary];
+    let place = (lattice.resolution as u64).pow(index_primary as u32);
+
+    for step in 0..=steps.min(lattice.resolution - 1) {
+        let fraction = step as f32 / (lattice.resolution - 1) as f32;
+
+        let mixture = secondary * ((fraction * primary + (1.0 - fraction) * inks.white) / inks.white);
+
+        walk(inks, mapping, lattice, steps - step, index_primary, mixture, index + step as u64 * place, function);
+    }
+}
+
+/// Mixes the given inks together, sampling fractions coarse-to-fine so that
+/// only the regions of ink space nearest to the given points, which must be in
+/// the mapping's space, get sampled at (at least) the given resolution. Returns
+/// the finest lattice sampled along with the secondary colors sampled on it.
+///
+/// Sampling starts from a coarse uniform grid. At each level, the grid points
+/// nearest to each of the given points are found, and only the neighborhoods of
+/// those grid points are sampled on a grid twice as fine, until the grid is at
+/// least as fine as the given resolution.
+///
+/// The searches for each of the points at each level count towards a phase of
+/// the given progress. If it's cancelled, sampling stops short, returning no
+/// secondary colors.
+pub fn adaptive(inks: &Inks, mapping: &Mapping, resolution: usize, points: Arc<Vec<Vector3>>, count_threads: usize, progress: &Progress) -> Option<(Lattice, Vec<Secondary>)> {
+    let count_primaries = inks.primaries.len();
+
+    let mut lattice = Lattice::new(
+        self::resolution(TARGET_ADAPTIVE_COARSE, count_primaries, inks.limit)?.min(resolution),
+        count_primaries
+    )?;
+
+    let mut count_levels = 0;
+    let mut resolution_level = lattice.resolution;
+    while resolution_level < resolution {
+        resolution_level = 2 * (resolution_level - 1) + 1;
+        count_levels += 1;
+    }
+
+    let phase = progress.phase("Sampling secondary colors", (count_levels * points.len()) as u64);
+
+    // Begin with every valid grid point of the coarsest level.
+    let mut secondaries = enumerate(inks, mapping, lattice, count_threads);
+
+    loop {
+        if lattice.resolution >= resolution {
+            phase.finish();
+
+            return Some((lattice, secondaries));
+        }
+
+        // Find which grid points are nearest to any of the points.
+        let rtree = RTree::bulk_load(secondaries);
+        let mapping_level = *mapping;
+        let nearest = parallel::map_progress(count_threads, points.clone(), &phase, move |point|
+            mapping_level.nearest_k(&rtree, *point, COUNT_NEAREST_ADAPTIVE).into_iter().map(|secondary| secondary.data).collect::<Vec<_>>()
+        );
+        let Some(nearest) = nearest else {
+            return Some((lattice, Vec::new()));
+        };
+        let nearest = nearest.into_iter().flatten().collect::<HashSet<_>>();
+
+        // Sample the neighborhoods of those grid points on the next level.
+        let lattice_next = Lattice::new(2 * (lattice.resolution - 1) + 1, count_primaries)?;
+
+        let steps_next = steps(lattice_next.resolution, count_primaries, inks.limit);
+        let width = 2 * RADIUS_ADAPTIVE + 1;
+
+        let mut candidates = HashSet::new();
+        for index in nearest {
+            let center = lattice.digits(index).into_iter().map(|digit| 2 * digit).collect::<Vec<_>>();
+
+            'neighbors: for number in 0..width.pow(count_primaries as u32) {
+                let mut neighbor = Vec::with_capacity(count_primaries);
+                let mut number = number;
+
+                for digit in center.iter() {
+                    match (*digit + number % width).checked_sub(RADIUS_ADAPTIVE) {
+                        Some(digit) if digit < lattice_next.resolution => neighbor.push(digit),
+                        _ => continue 'neighbors,
+                    }
+
+                    number /= width;
+                }
+
+                if neighbor.iter().sum::<usize>() <= steps_next {
+                    candidates.insert(lattice_next.index(&neighbor));
+                }
+            }
+        }
+
+        // Sort the candidates so that the results don't depend on the order
+        // the hash set happens to iterate in.
+        let mut candidates = candidates.into_iter().collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        let inks_level = inks.clone();
+        secondaries = parallel::map(count_threads, Arc::new(candidates), move |index| {
+            let secondary = inks_level.mix(&lattice_next.fractions(*index));
+
+            GeomWithData::new(mapping_level.point(secondary, inks_level.white), *index)
+        });
+        lattice = lattice_next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    use crate::progress::Cancellation;
+    use crate::testing;
+
+    #[test]
+    fn test_enumerate() {
+        let lattice = Lattice::new(10, 2).unwrap();
+
+        assert_eq!(100, enumerate(&testing::inks(2, f32::INFINITY), &Mapping::Colorimetric, lattice, 2).len());
+        assert_eq!(55, enumerate(&testing::inks(2, 1.0), &Mapping::Colorimetric, lattice, 2).len());
+        assert_eq!(1, enumerate(&testing::inks(2, 0.0), &Mapping::Colorimetric, lattice, 2).len());
+    }
+
+    /// Tests that the enumerated secondary colors are the mixtures of the
+    /// fractions their indices decode to.
+    #[test]
+    fn test_lattice() {
+        let inks = testing::inks(2, 1.5);
+        let lattice = Lattice::new(7, 2).unwrap();
+
+        for secondary in enumerate(&inks, &Mapping::Colorimetric, lattice, 2) {
+            let fractions = lattice.fractions(secondary.data);
+            let color = inks.mix(&fractions);
+
+            assert!(fractions.iter().sum::<f32>() <= 1.5);
+            assert_eq!(secondary.data, lattice.index(&lattice.digits(secondary.data)));
+
+            for index in 0..3 {
+                assert!((color[index] - secondary.geom()[index]).abs() <= 1e-6);
+            }
+        }
+
+        assert!(Lattice::new(1 << 16, 4).is_none());
+        assert!(Lattice::new(Lattice::resolution_max(4), 4).is_some());
+        assert!(resolution(usize::MAX, 1, 0.5).is_none());
+        assert!(resolution(1_000_000_000_000, 2, 1e-9).is_none());
+    }
+
+    /// Tests that the number of valid mixtures agrees with exhaustively
+    /// counting them.
+    #[allow(clippy::unused_unit)]
+    #[test_case(7, 1, f32::INFINITY)]
+    #[test_case(7, 1, 0.5)]
+    #[test_case(7, 3, f32::INFINITY)]
+    #[test_case(7, 3, 1.0)]
+    #[test_case(11, 4, 3.0)]
+    #[test_case(11, 4, 0.0)]
+    #[test_case(9, 5, 2.7)]
+    fn test_count(resolution: usize, count_primaries: usize, limit: f32) {
+        let steps = steps(resolution, count_primaries, limit);
+
+        let reference = (0..resolution.pow(count_primaries as u32)).filter(|number| {
+            let mut number = *number;
+            let mut total = 0;
+
+            for _ in 0..count_primaries {
+                total += number % resolution;
+                number /= resolution;
+            }
+
+            total <= steps
+        }).count();
+
+        assert_eq!(reference as u128, count(resolution, count_primaries, limit));
+    }
+
+    /// Tests that the resolution is the smallest that meets the target.
+    #[allow(clippy::unused_unit)]
+    #[test_case(10_000, 1, f32::INFINITY)]
+    #[test_case(10_000, 4, f32::INFINITY)]
+    #[test_case(10_000, 4, 3.0)]
+    #[test_case(10_000, 4, 0.5)]
+    #[test_case(100_000_000, 4, 3.0)]
+    #[test_case(100_000_000, 2, 0.01)]
+    #[test_case(100_000_000, 2, 1e-4)]
+    fn test_resolution(target: usize, count_primaries: usize, limit: f32) {
+        let resolution = resolution(target, count_primaries, limit).unwrap();
+
+        assert!(count(resolution, count_primaries, limit) >= target as u128);
+        assert!(count(resolution - 1, count_primaries, limit) < target as u128);
+    }
+
+    /// Tests that adaptive sampling finds the same nearest secondary colors as
+    /// uniform sampling at the same resolution.
+    #[test]
+    fn test_adaptive() {
+        let inks = testing::inks(2, f32::INFINITY);
+        let mapping = Mapping::Colorimetric;
+
+        let points = [[0.2, 0.3], [0.55, 0.9], [1.0, 0.05]].iter()
+            .map(|components| inks.mix(components))
+            .collect::<Vec<_>>();
+
+        let uniform = RTree::bulk_load(enumerate(&inks, &mapping, Lattice::new(253, 2).unwrap(), 2));
+        let (lattice, adaptive) = adaptive(&inks, &mapping, 253, Arc::new(points.clone()), 2, &Progress::silent(Cancellation::new())).unwrap();
+
+        assert_eq!(253, lattice.resolution);
+        assert!(adaptive.len() < 253 * 253 / 10);
+
+        let adaptive = RTree::bulk_load(adaptive);
+
+        for point in points {
+            assert_eq!(
+                mapping.nearest(&uniform, point).unwrap().data,
+                mapping.nearest(&adaptive, point).unwrap().data
+            );
+        }
+    }
+}
@@ -1,27 +1,73 @@
+mod accuracy;
+mod blend;
+mod cache;
+mod icc;
+mod image;
+mod inks;
+mod lut;
+mod mapping;
+mod optimize;
+mod parallel;
+mod progress;
+mod search;
+mod secondaries;
+mod shader;
+mod smooth;
+mod solver;
+mod store;
+#[cfg(test)]
+mod testing;
 mod vector;
 
 use lazy_static::lazy_static;
 use lcms2::ColorSpaceSignature;
+use lcms2::Flags;
+use lcms2::GlobalContext;
 use lcms2::Intent;
 use lcms2::PixelFormat;
 use lcms2::Profile;
 use lcms2::Transform;
 use maplit::hashmap;
 use rstar::primitives::GeomWithData;
-use rstar::RTree;
+use std::cell::OnceCell;
 use std::collections::HashMap;
 use std::env::args;
+use std::env::var;
+use std::ffi::OsStr;
+use std::fs::create_dir_all;
+use std::fs::remove_file;
 use std::fs::File;
 use std::io;
+use std::io::stderr;
 use std::io::BufWriter;
-use std::io::Write;
+use std::io::ErrorKind;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::process::exit;
 use std::sync::Arc;
-use std::sync::Mutex;
-use threadpool::ThreadPool;
+#[cfg(unix)]
+use std::sync::OnceLock;
 use unicase::UniCase;
 
+use accuracy::Statistic;
+use blend::Blend;
+use image::Layout;
+use inks::Inks;
+use lut::Format;
+use lut::Metadata;
+use mapping::Mapping;
+use optimize::Interpolation;
+use parallel::Transformation;
+use progress::Phase;
+use progress::Cancellation;
+use progress::Progress;
+use search::Backend;
+use search::Search;
+use secondaries::Lattice;
+use shader::Language;
+use store::Store;
+use vector::Precision;
+use vector::Scalar;
 use vector::Vector3;
 
 /// The default output 3D LUT size. A value of 64 is typical in professional
@@ -33,10 +79,30 @@ const SIZE_DEFAULT: usize = 64;
 /// reasonable amount of time.
 const TARGET_DEFAULT: usize = 100_000_000;
 
+/// The default target number when solving for continuous ink fractions. The
+/// secondary colors only seed the solver, so a coarse enumeration suffices.
+const TARGET_SOLVE_DEFAULT: usize = 10_000;
+
 /// The default ink limit. A value of infinity results in the program applying
 /// no ink limit restrictions on the generated secondary colors.
 const INKLIMIT_DEFAULT: f32 = f32::INFINITY;
 
+/// The default number of nearest secondary colors blended together. A value of
+/// 4 is the fewest that can enclose a 3D LUT color in a tetrahedron, and blends
+/// away stair-stepping without blurring the results much.
+const NEIGHBORS_DEFAULT: usize = 4;
+
+/// The environment variable that specifies the number of threads, if the
+/// command line doesn't. By default, every logical CPU gets a thread.
+const VARIABLE_THREADS: &str = "SEPARATIONS_THREADS";
+
+/// The exit code of a program stopped by an interrupt signal.
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// The token that an interrupt signal cancels.
+#[cfg(unix)]
+static CANCELLATION: OnceLock<Cancellation> = OnceLock::new();
+
 lazy_static! {
     /// The available named color profiles.
     static ref DATA_PROFILES: HashMap<UniCase<&'static str>, &'static [u8]> = hashmap! {
@@ -58,12 +124,70 @@ fn main() {
         exit(1);
     }
 
+    /// Errors out of the program as `errorout` does, deleting the given output
+    /// files, which would otherwise be left incomplete.
+    fn failout(paths: &[PathBuf], message: impl AsRef<str>) -> ! {
+        for path in paths {
+            let _ = remove_file(path);
+        }
+
+        errorout(message);
+    }
+
+    /// Exits the program once it's been cancelled, deleting the given output
+    /// files, none of which can be relied on to be complete.
+    fn cancelout(paths: &[PathBuf]) -> ! {
+        for path in paths {
+            let _ = remove_file(path);
+        }
+
+        // Clear away any progress left on the status line.
+        if stderr().is_terminal() {
+            eprint!("\r\x1B[2K");
+        }
+        eprintln!("\x1B[41m Cancelled \x1B[0m No output files were written.");
+
+        exit(EXIT_INTERRUPTED);
+    }
+
+    /// Parses the given number of threads, erroring out if it isn't a positive
+    /// integer.
+    fn parse_threads(value: &str) -> usize {
+        match value.trim().parse::<usize>() {
+            Ok(count_threads) if count_threads >= 1 => count_threads,
+            _ => errorout("Number of threads must be a positive integer."),
+        }
+    }
+
     let mut profile = None;
+    let mut name_profile = None;
     let mut path_output = None;
     let mut primaries = Vec::with_capacity(4);
-    let mut size = SIZE_DEFAULT;
-    let mut target = TARGET_DEFAULT;
+    let mut sizes = vec![SIZE_DEFAULT];
+    let mut target = None;
+    let mut tolerance = None;
+    let mut statistic = None;
     let mut inklimit = INKLIMIT_DEFAULT;
+    let mut mapping = None;
+    let mut weights = None;
+    let mut solve = false;
+    let mut blend = None;
+    let mut smoothing = None;
+    let mut interpolation = None;
+    let mut neighbors = None;
+    let mut adaptive = false;
+    let mut backend = None;
+    let mut path_store = None;
+    let mut directory_cache = None;
+    let mut count_threads = None;
+    let mut quiet = false;
+    let mut precision = Precision::Single;
+    let mut format = None;
+    let mut depth = None;
+    let mut shaper = false;
+    let mut layout = None;
+    let mut flip = false;
+    let mut abstract_lab = false;
 
     // Parse command line arguments. I probably could have saved myself a lot of
     // effort by using some preexisting argument parsing library, but this
@@ -95,7 +219,10 @@ fn main() {
 
             // Profile
             "-p" | "--profile" => {
-                profile = Some(match argument_next() {
+                let identifier = argument_next();
+                name_profile = Some(identifier.clone());
+
+                profile = Some(match identifier {
                     // sRGB
                     identifier if identifier.eq_ignore_ascii_case("sRGB") =>
                         Profile::new_srgb(),
@@ -143,25 +270,57 @@ fn main() {
                 ]));
             },
 
-            // 3D LUT size
+            // 3D LUT sizes
             "-s" | "--size" => {
-                size = argument_next().parse::<usize>().unwrap_or_else(|_| {
-                    errorout("3D LUT size must be an integer greater than or equal to 2.")
-                });
+                // Parses the given size.
+                let parse_size = |size: &str| {
+                    let size = size.trim().parse::<usize>().unwrap_or_else(|_| {
+                        errorout("3D LUT size must be an integer greater than or equal to 2.")
+                    });
 
-                if size < 2 {
-                    errorout("3D LUT size must be an integer greater than or equal to 2.");
-                }
+                    if size < 2 {
+                        errorout("3D LUT size must be an integer greater than or equal to 2.");
+                    }
+
+                    size
+                };
+
+                sizes = argument_next().split(',').map(parse_size).collect();
+                sizes.sort_unstable();
+                sizes.dedup();
             },
             // Target secondaries
             "-t" | "--target" => {
-                target = argument_next().parse::<usize>().unwrap_or_else(|_| {
+                let value = argument_next().parse::<usize>().unwrap_or_else(|_| {
                     errorout("Target number must be a positive integer.")
                 });
 
-                if target < 1 {
+                if value < 1 {
                     errorout("Target number must be a positive integer.");
                 }
+
+                target = Some(value);
+            },
+            // Tolerance
+            "--tolerance" => {
+                let value = argument_next().parse::<f32>().unwrap_or_else(|_| {
+                    errorout("Tolerance must be a positive number.")
+                });
+
+                if value <= 0.0 || !value.is_finite() {
+                    errorout("Tolerance must be a positive number.");
+                }
+
+                tolerance = Some(value);
+            },
+            // Tolerance statistic
+            "--statistic" => {
+                statistic = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "max" => Statistic::Maximum,
+                    "p95" => Statistic::Percentile95,
+
+                    identifier => errorout(format!("Unknown tolerance statistic \x1B[96m{}\x1B[0m.", identifier)),
+                });
             },
             // Ink limit
             "-l" | "--limit" => {
@@ -173,6 +332,170 @@ fn main() {
                     errorout("Ink limit must be non-negative number.");
                 }
             },
+            // Mapping mode
+            "-m" | "--mapping" => {
+                mapping = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "colorimetric" => Mapping::Colorimetric,
+                    "lab" => Mapping::Weighted(mapping::WEIGHTS_LAB),
+                    "lightness" => Mapping::Weighted(mapping::WEIGHTS_LIGHTNESS),
+                    "hue" => Mapping::Weighted(mapping::WEIGHTS_HUE),
+
+                    identifier => errorout(format!("Unknown mapping mode \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Adaptive sampling
+            "--adaptive" => {
+                adaptive = true;
+            },
+            // Secondary color cache
+            "--cache" => {
+                directory_cache = Some(PathBuf::from(argument_next()));
+            },
+            // Search structure
+            "--search" => {
+                backend = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "rtree" => Backend::RTree,
+                    "grid" => Backend::Grid,
+                    "kdtree" => Backend::KdTree,
+                    "table" => Backend::Table,
+
+                    identifier => errorout(format!("Unknown search structure \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Secondary color store
+            "--store" => {
+                path_store = Some(PathBuf::from(argument_next()));
+            },
+            // Continuous solver
+            "--solve" => {
+                solve = true;
+            },
+            // CLF shaper
+            "--shaper" => {
+                shaper = true;
+            },
+            // Strip layout
+            "--layout" => {
+                layout = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "horizontal" => Layout::Horizontal,
+                    "vertical" => Layout::Vertical,
+
+                    identifier => errorout(format!("Unknown strip layout \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Strip flipping
+            "--flip" => {
+                flip = true;
+            },
+            // Abstract ICC profiles
+            "--abstract" => {
+                abstract_lab = true;
+            },
+            // Smoothing
+            "--smooth" => {
+                let value = argument_next().parse::<f32>().unwrap_or_else(|_| {
+                    errorout("Smoothing tolerance must be a non-negative number.")
+                });
+
+                if value < 0.0 || !value.is_finite() {
+                    errorout("Smoothing tolerance must be a non-negative number.");
+                }
+
+                smoothing = Some(value);
+            },
+            // Interpolation-aware optimization
+            "--optimize" => {
+                interpolation = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "trilinear" => Interpolation::Trilinear,
+                    "tetrahedral" => Interpolation::Tetrahedral,
+
+                    identifier => errorout(format!("Unknown interpolation method \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Blending method
+            "--blend" => {
+                blend = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "idw" => Blend::InverseDistance,
+                    "barycentric" => Blend::Barycentric,
+
+                    identifier => errorout(format!("Unknown blending method \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Blended secondaries
+            "--neighbors" => {
+                let value = argument_next().parse::<usize>().unwrap_or_else(|_| {
+                    errorout("Number of neighbors must be a positive integer.")
+                });
+
+                if value < 1 {
+                    errorout("Number of neighbors must be a positive integer.");
+                }
+
+                neighbors = Some(value);
+            },
+            // Threads
+            "--threads" => {
+                count_threads = Some(parse_threads(&argument_next()));
+            },
+            // Output format
+            "-f" | "--format" => {
+                format = Some(match argument_next().to_ascii_lowercase().as_str() {
+                    "cube" => Format::Cube,
+                    "3dl" => Format::Autodesk { depth: lut::DEPTH_AUTODESK_DEFAULT },
+                    "clf" => Format::Clf { shaper: false, interpolation: Interpolation::Trilinear },
+                    "csp" => Format::Cinespace,
+                    "vf" => Format::Nuke,
+                    "hald" => Format::Hald { depth: None },
+                    "strip" => Format::Strip { layout: Layout::Horizontal, flip: false, depth: None },
+                    "glsl" => Format::Shader { language: Language::Glsl },
+                    "hlsl" => Format::Shader { language: Language::Hlsl },
+                    "wgsl" => Format::Shader { language: Language::Wgsl },
+                    "icc" => Format::Icc { abstract_lab: false },
+                    "nclr" => Format::Nclr,
+
+                    identifier => errorout(format!("Unknown output format \x1B[96m{}\x1B[0m.", identifier)),
+                });
+            },
+            // Output bit depth
+            "--depth" => {
+                depth = Some(argument_next().parse::<u32>().unwrap_or_else(|_|
+                    errorout("Output depth must be a positive integer.")
+                ));
+            },
+            // Precision
+            "--precision" => {
+                precision = match argument_next().to_ascii_lowercase().as_str() {
+                    "f32" => Precision::Single,
+                    "f64" => Precision::Double,
+
+                    identifier => errorout(format!("Unknown precision \x1B[96m{}\x1B[0m.", identifier)),
+                };
+            },
+            // Progress
+            "-q" | "--quiet" => {
+                quiet = true;
+            },
+            // Mapping weights
+            "-w" | "--weights" => {
+                // Parses the given weight.
+                let parse_weight = |weight: String| {
+                    let weight = weight.parse::<f32>().unwrap_or_else(|_| {
+                        errorout("Mapping weights must be positive numbers.")
+                    });
+
+                    if weight <= 0.0 || !weight.is_finite() {
+                        errorout("Mapping weights must be positive numbers.");
+                    }
+
+                    weight
+                };
+
+                weights = Some(Vector3([
+                    parse_weight(argument_next()),
+                    parse_weight(argument_next()),
+                    parse_weight(argument_next())
+                ]));
+            },
             
             // Unknown option
             option => {
@@ -191,211 +514,725 @@ fn main() {
     if primaries.is_empty() {
         errorout("No primary colors were specified. Use \x1B[93m--color\x1B[0m to specify a primary color.");
     }
-    
-    // TODO what should resolution be?
-    let count_colors_lut = size.pow(3);
-    let resolution = (target as f64).powf(1.0 / primaries.len() as f64).ceil() as usize;
-    let count_secondaries = resolution.pow(primaries.len() as u32);
 
-    // Prepare profile transformations.
+    // Custom weights apply to a weighted mapping mode, defaulting to CIELAB.
+    let mapping = match (mapping, weights) {
+        (Some(Mapping::Colorimetric), Some(_)) =>
+            errorout("Mapping weights cannot be used with the colorimetric mapping mode."),
+        (_, Some(weights)) => Mapping::Weighted(weights),
+        (Some(mapping), None) => mapping,
+        (None, None) => Mapping::Colorimetric,
+    };
+
+    // A tolerance stands in for the target number, measured by a statistic.
+    let statistic = match (tolerance, target, statistic) {
+        (Some(_), Some(_), _) =>
+            errorout("The target number cannot be used together with a tolerance."),
+        (None, _, Some(_)) =>
+            errorout("The tolerance statistic can only be used with \x1B[93m--tolerance\x1B[0m."),
+        (_, _, statistic) => statistic.unwrap_or(Statistic::Maximum),
+    };
+
+    if blend.is_none() && neighbors.is_some() {
+        errorout("The number of neighbors can only be used with \x1B[93m--blend\x1B[0m.");
+    }
+    let neighbors = neighbors.unwrap_or(NEIGHBORS_DEFAULT);
+    if blend == Some(Blend::Barycentric) && neighbors > blend::NEIGHBORS_BARYCENTRIC_MAX {
+        errorout(format!("Barycentric blending can blend at most {} neighbors.", blend::NEIGHBORS_BARYCENTRIC_MAX));
+    }
+    if blend.is_some() && smoothing.is_some() {
+        errorout("Blending cannot be used together with smoothing.");
+    }
+
+    // A cache already keeps its secondary colors in a memory-mapped file.
+    if path_store.is_some() && directory_cache.is_some() {
+        errorout("A store cannot be used together with a cache.");
+    }
+
+    // Stores are searched in place, so there's no choosing how.
+    if backend.is_some() && (path_store.is_some() || directory_cache.is_some()) {
+        errorout("The search structure cannot be used together with a store or a cache.");
+    }
+    let backend = backend.unwrap_or(Backend::RTree);
+
+    // The command line takes precedence over the environment.
+    let count_threads = count_threads
+        .or_else(|| var(VARIABLE_THREADS).ok().map(|value| parse_threads(&value)))
+        .unwrap_or_else(num_cpus::get);
+
+    // Interrupting the program cancels it, so that it can clean up after
+    // itself rather than leave half-written files behind.
+    let cancellation = Cancellation::new();
+    handle_interrupts(&cancellation);
+
+    let progress = if quiet { Progress::silent(cancellation) } else { Progress::stderr(cancellation) };
+
+    let target = target.unwrap_or(if solve { TARGET_SOLVE_DEFAULT } else { TARGET_DEFAULT });
+
+    // The resolution that meets a tolerance is only known once the inks are.
+    let resolution = tolerance.is_none().then(||
+        secondaries::resolution(target, primaries.len(), inklimit).unwrap_or_else(||
+            errorout("Too many secondary colors. Specify a smaller target number.")
+        )
+    );
+
+    // Prepare profile transformations. Without a cache, they can be shared
+    // between threads.
     let profile_xyz = Profile::new_xyz();
-    let transformation_reverse = Transform::new(
+    let transformation_reverse = Arc::new(Transform::new_flags_context(
+        GlobalContext::new(),
         &profile,
         PixelFormat::RGB_FLT,
         &profile_xyz,
         PixelFormat::XYZ_FLT,
-        Intent::AbsoluteColorimetric
-    ).unwrap();
-    let transformation_forward = Transform::new(
+        Intent::AbsoluteColorimetric,
+        Flags::NO_CACHE
+    ).unwrap());
+    let transformation_forward = Arc::new(Transform::new_flags_context(
+        GlobalContext::new(),
         &profile_xyz,
         PixelFormat::XYZ_FLT,
         &profile,
         PixelFormat::RGB_FLT,
-        Intent::AbsoluteColorimetric
-    ).unwrap();
+        Intent::AbsoluteColorimetric,
+        Flags::NO_CACHE
+    ).unwrap());
     
-    // Returns a new output record.
-    let new_output = |path: &PathBuf| -> (File, Vec<Vector3>) {
-        let file = File::create(path).unwrap_or_else(|error|
-            errorout(format!("Could not create output 3D LUT file \x1B[96m{}\x1B[0m: {}.", path.display(), error))
-        );
+    let extension = path_output.extension().unwrap_or_default();
 
-        (file, Vec::with_capacity(count_colors_lut))
+    // The output format is told by the output file's extension, unless it's
+    // specified, defaulting to the .cube format.
+    let options = lut::Options {
+        depth,
+        shaper,
+        interpolation,
+        layout,
+        flip,
+        abstract_lab,
     };
+    let format = format.or_else(|| Format::from_extension(extension)).unwrap_or(Format::Cube).with_options(options)
+        .and_then(|format| format.check(primaries.len(), &sizes).map(|_| format))
+        .unwrap_or_else(|message| errorout(message));
 
-    let mut outputs = Vec::with_capacity(1 + 2 * primaries.len());
+    // Every output records the inputs it was generated from, if its format
+    // records anything.
+    let mut info = vec![(String::from("Profile"), name_profile.unwrap())];
+    info.extend(primaries.iter().map(|primary| (String::from("Primary"), format!("{} {} {}", primary[0], primary[1], primary[2]))));
+    info.push((String::from("InkLimit"), if inklimit.is_finite() { inklimit.to_string() } else { String::from("None") }));
+    info.push(match tolerance {
+        Some(tolerance) => (String::from("Tolerance"), tolerance.to_string()),
+        None => (String::from("Target"), target.to_string()),
+    });
 
-    let extension = path_output.extension().unwrap_or_default();
-    let stem = path_output.file_stem().unwrap();
+    let data_profile = profile.icc().unwrap();
 
-    outputs.push(new_output(&path_output));
+    // Returns the metadata of the output with the given stem and description,
+    // which is a mask or not.
+    let metadata = |stem: &OsStr, description: String, mask: bool| Metadata {
+        name: stem.to_string_lossy().into_owned(),
+        description,
+        info: info.clone(),
+        mask,
+        profile: data_profile.clone(),
+    };
 
-    // Prepare a primary and mask 3D LUT for each primary color.
-    for index_component in 0..primaries.len() {
-        let mut stem_component = stem.to_os_string();
-        stem_component.push("_");
-        stem_component.push(index_component.to_string());
+    let mut paths_output = Vec::with_capacity(sizes.len() * (1 + 2 * primaries.len()));
 
-        let mut path_component_main = path_output.with_file_name(&stem_component);
-        path_component_main.set_extension(extension);
-        
-        stem_component.push("m");
+    // Prepare an output for each size, told apart by a size suffix if there
+    // is more than one.
+    let outputs = sizes.iter().map(|&size| {
+        let mut stem = path_output.file_stem().unwrap().to_os_string();
+        if sizes.len() > 1 {
+            stem.push(format!("_{}pt", size));
+        }
 
-        let mut path_component_mask = path_output.with_file_name(&stem_component);
-        path_component_mask.set_extension(extension);
+        // Returns the path of the file with the given stem.
+        let path = |stem: &OsStr| {
+            let mut path = path_output.with_file_name(stem);
+            path.set_extension(extension);
 
-        outputs.push(new_output(&path_component_main));
-        outputs.push(new_output(&path_component_mask));
-    }
+            path
+        };
+
+        // Creates the file of the 3D LUT with the given index, or of every 3D
+        // LUT at once.
+        let output = |index: Option<usize>| {
+            // Each primary color has a primary and mask 3D LUT.
+            let (path, metadata) = match index {
+                None => (path(&stem), metadata(&stem, String::from("Color separation of every primary color"), false)),
+                Some(0) => (path(&stem), metadata(&stem, String::from("Composite of every primary color of the color separation"), false)),
+                Some(index) => {
+                    let index_component = (index - 1) / 2;
+                    let primary = primaries[index_component];
+                    let mask = index % 2 == 0;
+
+                    let mut stem_component = stem.clone();
+                    stem_component.push(format!("_{}{}", index_component, if mask { "m" } else { "" }));
+
+                    let name_primary = format!("primary color {} ({} {} {})", index_component, primary[0], primary[1], primary[2]);
+                    let description = if mask { format!("Mask of the {} of the color separation", name_primary) } else { format!("The {} of the color separation", name_primary) };
+
+                    (path(&stem_component), metadata(&stem_component, description, mask))
+                },
+            };
+
+            let file = File::create(&path).unwrap_or_else(|error|
+                failout(&paths_output, format!("Could not create output 3D LUT file \x1B[96m{}\x1B[0m: {}.", path.display(), error))
+            );
+            paths_output.push(path);
+
+            (BufWriter::new(file), metadata)
+        };
+
+        let inks = primaries.iter().map(|primary| primary.cast::<f64>() / 255.0).collect();
+
+        (size, format.writer(output, precision, inks))
+    }).collect::<Vec<_>>();
+
+    // Colors computed in double precision start out from the primary colors
+    // and white moved into XYZ space in double precision too. Only the output
+    // stage is computed in double precision, from ink fractions found in
+    // single precision.
+    let double = (precision == Precision::Double).then(|| {
+        let transformation_reverse: Transformation<f64> = Transform::new_flags_context(
+            GlobalContext::new(),
+            &profile,
+            PixelFormat::RGB_DBL,
+            &profile_xyz,
+            PixelFormat::XYZ_DBL,
+            Intent::AbsoluteColorimetric,
+            Flags::NO_CACHE
+        ).unwrap();
+        let transformation_forward = Arc::new(Transform::new_flags_context(
+            GlobalContext::new(),
+            &profile_xyz,
+            PixelFormat::XYZ_DBL,
+            &profile,
+            PixelFormat::RGB_DBL,
+            Intent::AbsoluteColorimetric,
+            Flags::NO_CACHE
+        ).unwrap());
+
+        let mut colors = vec![Vector3([1.0, 1.0, 1.0])];
+        colors.extend(primaries.iter().map(|primary| primary.cast::<f64>() / 255.0));
+        transformation_reverse.transform_in_place(&mut colors);
+
+        let inks = Inks {
+            white: colors[0],
+            primaries: colors[1..].to_vec(),
+            limit: inklimit,
+        };
+
+        (inks, transformation_forward)
+    });
 
     // TODO get component type, is 255 really it? Maybe someone wants to specify
     // colors from a range of 0-1?
     for primary in primaries.iter_mut() {
         *primary /= 255.0;
     }
-    
-    // Generate the origin 3D LUT colors in their correct order.
-    let mut colors_lut = Vec::with_capacity(count_colors_lut);
-    for index_blue in 0..size {
-        let component_blue = (index_blue % size) as f32 / (size - 1) as f32;
-
-        for index_green in 0..size {
-            let component_green = (index_green % size) as f32 / (size - 1) as f32;
-
-            for index_red in 0..size {
-                let component_red = (index_red % size) as f32 / (size - 1) as f32;
-
-                colors_lut.push(Vector3([component_red, component_green, component_blue]));
-            }
-        }
-    }
 
     let mut white = vec![Vector3([1.0, 1.0, 1.0])];
 
     // Move all of the colors into XYZ space.
-    // TODO probably not worth multithreading this but maybe?
     transformation_reverse.transform_in_place(&mut primaries);
     transformation_reverse.transform_in_place(&mut white);
-    transformation_reverse.transform_in_place(&mut colors_lut);
     let white = white[0];
 
-    // Mix the primary colors together, applying subtractive color mixing.
-    // There's probably an algorithm superior to the one used below, one that
-    // can optimize for small ink limits. It is almost certainly not worth
-    // trying to find it. This area of code is not likely to benefit a lot from
-    // multithreading, so I'm not gonna bother.
-    let mut secondaries = Vec::with_capacity(count_secondaries);
-    'secondaries: for mut number in 0..count_secondaries {
-        let mut secondary = white;
-        let mut components = Vec::with_capacity(primaries.len());
-        let mut total = 0.0;
-
-        for primary in primaries.iter() {
-            let fraction = (number % resolution) as f32 / (resolution - 1) as f32;
-
-            total += fraction;
-            
-            // Current secondary color violates the ink limit. Immediately
-            // abandon this particular mixture of primaries.
-            if total > inklimit {
-                continue 'secondaries;
+    // Returns the origin 3D LUT colors of the slice of a 3D LUT of the given
+    // size with the given blue index, in XYZ space.
+    let slice_lut = |size: usize, index_blue: usize| -> Vec<Vector3> {
+        parallel::transform(count_threads, &transformation_reverse, colors_slice(size, index_blue))
+    };
+
+    let inks = Inks {
+        white,
+        primaries,
+        limit: inklimit,
+    };
+
+    // Sample ink fractions as coarsely as the tolerance allows.
+    let (resolution, error) = match tolerance {
+        Some(tolerance) => {
+            let (resolution, error) = accuracy::resolution(&inks, tolerance, statistic, count_threads).unwrap_or_else(|finest| match finest {
+                Some((resolution, error)) => failout(&paths_output, format!(
+                    "Could not meet the tolerance with at most {} secondary colors, coming within {:.3} \u{394}E sampling {} fractions per ink. Specify a larger tolerance.",
+                    accuracy::COUNT_MAX, error, resolution
+                )),
+                None => failout(&paths_output, "Too many secondary colors. Specify a larger tolerance."),
+            });
+
+            (resolution, Some(error))
+        },
+        None => (resolution.unwrap(), None),
+    };
+
+    // Refining around the 3D LUT colors is the one place that needs all of
+    // them at once, of every size. Optimizing also separates the colors of a
+    // finer grid, which takes in the 3D LUT colors, so that grid is refined
+    // around instead.
+    let points_lut = adaptive.then(|| sizes.iter()
+        .map(|&size| match interpolation {
+            Some(_) => optimize::SUBDIVISIONS * (size - 1) + 1,
+            None => size,
+        })
+        .flat_map(|size| (0..size).flat_map(move |index_blue| slice_lut(size, index_blue)))
+        .map(|color| mapping.point(color, white))
+        .collect::<Vec<_>>()
+    );
+
+    // Look for secondary colors cached by an earlier run with the same inputs.
+    let path_cache = directory_cache.map(|directory_cache| {
+        create_dir_all(&directory_cache).unwrap_or_else(|error|
+            failout(&paths_output, format!("Could not create cache directory \x1B[96m{}\x1B[0m: {}.", directory_cache.display(), error))
+        );
+
+        cache::path(&directory_cache, &inks, &mapping, resolution, points_lut.as_deref())
+    });
+    let cached = path_cache.as_ref().and_then(|path_cache| Store::open(path_cache).ok());
+
+    let (lattice, search): (Lattice, Arc<dyn Search>) = match cached {
+        Some(store) => (store.lattice, Arc::new(store)),
+        None => {
+            // Secondary colors bound for the cache are put together in a store
+            // next to it, which only takes its place once it's complete. The
+            // store is named after this process, so that runs sharing the
+            // cache never write to each other's.
+            let path_store = match &path_cache {
+                Some(path_cache) => Some(path_cache.with_extension(format!("{}.partial", std::process::id()))),
+                None => path_store,
+            };
+
+            // Mix the primary colors together, applying subtractive color
+            // mixing.
+            let (lattice, secondaries) = match points_lut {
+                Some(points_lut) => secondaries::adaptive(&inks, &mapping, resolution, Arc::new(points_lut), count_threads, &progress).unwrap_or_else(||
+                    failout(&paths_output, "Too many secondary colors. Specify a smaller target number.")
+                ),
+                None => {
+                    let lattice = Lattice::new(resolution, inks.primaries.len()).unwrap_or_else(||
+                        failout(&paths_output, "Too many secondary colors. Specify a smaller target number.")
+                    );
+
+                    // A store never needs the secondary colors all in memory at
+                    // once.
+                    let secondaries = match path_store {
+                        Some(_) => Vec::new(),
+                        None => {
+                            let count = secondaries::count(lattice.resolution, lattice.count_primaries, inks.limit) as usize;
+                            let phase = progress.phase("Generating secondary colors", count as u64);
+
+                            let mut secondaries = Vec::with_capacity(count);
+                            secondaries::enumerate_with(&inks, &mapping, lattice, count_threads, Some(&phase), &mut |point, index| {
+                                secondaries.push(GeomWithData::new(point, index));
+                            });
+
+                            phase.finish();
+
+                            secondaries
+                        },
+                    };
+
+                    (lattice, secondaries)
+                },
+            };
+
+            if progress.is_cancelled() {
+                drop(outputs);
+                cancelout(&paths_output);
             }
 
-            secondary *= (fraction * *primary + (1.0 - fraction) * white) / white;
+            // Populate the structure to search the secondary colors with.
+            let search: Arc<dyn Search> = match &path_store {
+                Some(path_store) => {
+                    let store = if adaptive {
+                        Store::create(path_store, lattice, secondaries.len(), |function| {
+                            for secondary in secondaries.iter() {
+                                function(*secondary.geom(), secondary.data);
+                            }
+                        })
+                    }
+                    else {
+                        let count = secondaries::count(lattice.resolution, lattice.count_primaries, inks.limit) as usize;
+
+                        let phase = progress.phase("Generating secondary colors", count as u64);
+
+                        let store = Store::create(path_store, lattice, count, |mut function| {
+                            secondaries::enumerate_with(&inks, &mapping, lattice, count_threads, Some(&phase), &mut function);
+                        });
 
-            components.push(fraction);
+                        phase.finish();
 
-            number /= resolution;
+                        store
+                    };
+
+                    let mut store = match store {
+                        Ok(store) => store,
+                        Err(error) if error.kind() == ErrorKind::Interrupted => {
+                            drop(outputs);
+                            cancelout(&paths_output);
+                        },
+                        Err(error) => failout(&paths_output, format!("Could not create secondary color store file \x1B[96m{}\x1B[0m: {}.", path_store.display(), error)),
+                    };
+
+                    if let Some(path_cache) = &path_cache {
+                        store.persist(path_cache).unwrap_or_else(|error|
+                            failout(&paths_output, format!("Could not write cache file \x1B[96m{}\x1B[0m: {}.", path_cache.display(), error))
+                        );
+                    }
+
+                    Arc::new(store)
+                },
+                None => {
+                    // The build can't report how far along it is, so it's only
+                    // timed.
+                    let phase = progress.phase("Building search structure", 0);
+
+                    let search = search::build(backend, &mapping, secondaries, count_threads);
+
+                    phase.finish();
+
+                    Arc::from(search)
+                },
+            };
+
+            (lattice, search)
+        },
+    };
+
+    let arc_inks = Arc::new(inks);
+    let count_outputs = 1 + 2 * arc_inks.primaries.len();
+
+    // Each 3D LUT color is looked up, solved for and transformed once, along
+    // with each color of the finer grid that it's fit to if optimizing.
+    let count_colors = sizes.iter().map(|&size| match interpolation {
+        Some(_) => size.pow(3) + (optimize::SUBDIVISIONS * (size - 1) + 1).pow(3),
+        None => size.pow(3),
+    }).sum::<usize>() as u64;
+    let count_written = sizes.iter().map(|&size| size.pow(3) * count_outputs).sum::<usize>() as u64;
+
+    // Each phase starts once its work first begins, rather than all of them
+    // starting at once.
+    let phase_lookup = OnceCell::new();
+    let phase_solve = OnceCell::new();
+    let phase_transform = OnceCell::new();
+    let phase_optimize = OnceCell::new();
+    let phase_write = OnceCell::new();
+
+    let start_lookup = || phase_lookup.get_or_init(|| progress.phase("Looking up 3D LUT colors", count_colors));
+    let start_solve = || phase_solve.get_or_init(|| progress.phase("Solving for ink fractions", count_colors));
+    let start_transform = || phase_transform.get_or_init(|| progress.phase("Transforming 3D LUT colors", count_colors * (1 + arc_inks.primaries.len()) as u64));
+    let start_optimize = || phase_optimize.get_or_init(|| progress.phase("Optimizing 3D LUTs", (sizes.len() * count_outputs) as u64));
+    let start_write = || phase_write.get_or_init(|| progress.phase("Writing 3D LUTs", count_written));
+
+    // Returns the points, in the mapping's space, of the origin 3D LUT colors of
+    // the slice of a 3D LUT of the given size with the given blue index.
+    let points_slice = |size: usize, index_blue: usize| -> Vec<Vector3> {
+        slice_lut(size, index_blue).into_iter().map(|color| mapping.point(color, white)).collect()
+    };
+
+    // Refines the given ink fractions of each of the given points into exact
+    // fractions, if solving.
+    let refine = |points: Vec<Vector3>, components: Vec<Vec<f32>>| -> io::Result<Vec<Vec<f32>>> {
+        if !solve {
+            return Ok(components);
         }
 
-        secondaries.push(GeomWithData::new(secondary, (secondary, components)));
-    }
+        let inks = arc_inks.clone();
+        let items = points.into_iter().zip(components).collect::<Vec<_>>();
 
-    // Populate the RTree.
-    let rtree = RTree::bulk_load(secondaries);
+        parallel::map_progress(count_threads, Arc::new(items), start_solve(), move |(point_lut, components)|
+            solver::solve(&inks, &mapping, *point_lut, components)
+        ).ok_or_else(interrupted)
+    };
 
-    let count_threads = num_cpus::get();
-    let threadpool = ThreadPool::new(count_threads);
+    // Returns the ink fractions of each of the given points.
+    let separate = |points: Vec<Vector3>| -> io::Result<Vec<Vec<f32>>> {
+        let inks = arc_inks.clone();
+        let search = search.clone();
 
-    let arc_results = Arc::new(Mutex::from(vec![Vec::new(); count_threads])); // TODO pointless initialized memory
-    let arc_primaries = Arc::new(primaries);
-    let arc_colors_lut = Arc::new(colors_lut);
-    let arc_rtree = Arc::new(rtree);
+        let components = parallel::map_progress(count_threads, Arc::new(points.clone()), start_lookup(), move |point_lut| {
+            match blend {
+                None => lattice.fractions(search.nearest(&mapping, *point_lut)),
+                // Blend the fractions of several of the nearest secondary
+                // colors instead of snapping to one of them.
+                Some(blend) => {
+                    let neighbors = search.nearest_k(&mapping, *point_lut, neighbors).into_iter().map(|index| {
+                        let fractions = lattice.fractions(index);
 
-    for index_thread in 0..count_threads {
-        let results = arc_results.clone();
-        let primaries = arc_primaries.clone();
-        let colors_lut = arc_colors_lut.clone();
-        let rtree = arc_rtree.clone();
+                        (mapping.point(inks.mix(&fractions), white), fractions)
+                    }).collect::<Vec<_>>();
 
-        threadpool.execute(move || {
-            let start = index_thread * colors_lut.len() / count_threads;
-            let end = (index_thread + 1) * colors_lut.len() / count_threads;
+                    blend::blend(blend, &mapping, *point_lut, &neighbors)
+                },
+            }
+        }).ok_or_else(interrupted)?;
 
-            let mut result = vec![Vec::with_capacity(end - start); 1 + 2 * primaries.len()];
+        refine(points, components)
+    };
 
-            // Generate 3D LUTs for this thread's designated allocation.
-            for index in start..end {
-                let color_lut = colors_lut[index];
+    // Returns the ink fractions of each of the 3D LUT colors of a 3D LUT of the
+    // given size.
+    let separate_lut = |size: usize| -> io::Result<Vec<Vec<f32>>> {
+        let mut components = Vec::with_capacity(size.pow(3));
 
-                let data_secondary = rtree.nearest_neighbor(&color_lut).unwrap();
+        for index_blue in 0..size {
+            components.append(&mut separate(points_slice(size, index_blue))?);
+        }
 
-                let (secondary, components) = &data_secondary.data;
+        Ok(components)
+    };
 
-                result[0].push(*secondary);
+    // Returns the colors of each 3D LUT at the 3D LUT colors with the given ink
+    // fractions, or just those of the 3D LUT with the given index, computed
+    // with the chosen precision.
+    let colors = |components: &[Vec<f32>], index_output: Option<usize>| -> io::Result<Vec<Vec<Vector3<f64>>>> {
+        match &double {
+            None => colors_lut(&arc_inks, &transformation_forward, components, index_output, count_threads, start_transform()),
+            Some((inks, transformation_forward)) => colors_lut(inks, transformation_forward, components, index_output, count_threads, start_transform()),
+        }
+    };
 
-                for index_primary in 0..primaries.len() {
-                    let primary = primaries[index_primary];
-                    let fraction = components[index_primary];
+    // Generates and writes the 3D LUT files of each size, all from the same
+    // secondary colors. The 3D LUTs are generated one slice of constant blue at
+    // a time, and each slice is written out to every file before the next is
+    // generated, so that memory use doesn't grow with the size of the 3D LUTs,
+    // unless smoothing or optimizing.
+    //
+    // I mean, it's kinda like a try-catch block, right?
+    let result = (|| -> io::Result<()> {
+        for (size, mut output) in outputs {
+            output.begin(size)?;
 
-                    let color = fraction * primary + (1.0 - fraction) * white;
+            if smoothing.is_none() && interpolation.is_none() {
+                for index_blue in 0..size {
+                    let colors_slice = colors(&separate(points_slice(size, index_blue))?, None)?;
 
-                    result[2 * index_primary + 1].push(color);
-                    result[2 * index_primary + 2].push(Vector3([fraction, fraction, fraction]));
+                    for (index_output, colors_output) in colors_slice.into_iter().enumerate() {
+                        output.write_colors(index_output, &colors_output)?;
+                        start_write().advance(colors_output.len() as u64);
+                    }
                 }
             }
+            else {
+                let components = match smoothing {
+                    None => separate_lut(size)?,
+                    // Smoothing looks at the neighbors of every 3D LUT color,
+                    // so the candidate recipes of the whole 3D LUT are needed
+                    // at once.
+                    Some(tolerance) => {
+                        let mut candidates = Vec::with_capacity(size.pow(3));
+
+                        for index_blue in 0..size {
+                            let inks = arc_inks.clone();
+                            let search = search.clone();
+
+                            candidates.append(&mut parallel::map_progress(count_threads, Arc::new(points_slice(size, index_blue)), start_lookup(), move |point_lut| {
+                                smooth::candidates(&inks, lattice, &search.nearest_k(&mapping, *point_lut, smooth::COUNT_CANDIDATES), tolerance)
+                            }).ok_or_else(interrupted)?);
+                        }
+
+                        let components = smooth::smooth(size, lattice.count_primaries, &candidates);
+                        drop(candidates);
+
+                        let mut components_refined = Vec::with_capacity(components.len());
+                        for (index_blue, components_slice) in components.chunks(size * size).enumerate() {
+                            components_refined.append(&mut refine(points_slice(size, index_blue), components_slice.to_vec())?);
+                        }
 
-            let mut results = results.lock().unwrap();
-            results[index_thread] = result;
-        });
+                        components_refined
+                    },
+                };
+
+                let mut colors_lut = colors(&components, None)?;
+                drop(components);
+
+                // Fit each 3D LUT, once interpolated, to the 3D LUTs separated
+                // at a finer grid.
+                if let Some(interpolation) = interpolation {
+                    let size_fine = optimize::SUBDIVISIONS * (size - 1) + 1;
+
+                    let components_fine = separate_lut(size_fine)?;
+
+                    for (index_output, colors_output) in colors_lut.iter_mut().enumerate() {
+                        let colors_fine = Arc::new(colors(&components_fine, Some(index_output))?.swap_remove(index_output));
+                        let colors_nodes = Arc::new(std::mem::take(colors_output));
+
+                        let phase_optimize = start_optimize();
+                        if phase_optimize.is_cancelled() {
+                            return Err(interrupted());
+                        }
+
+                        // Masks are gray, so one of their channels is enough.
+                        let channels_optimize = if index_output > 0 && index_output % 2 == 0 { vec![0] } else { vec![0, 1, 2] };
+
+                        let channels = parallel::map(channels_optimize.len().min(count_threads), Arc::new(channels_optimize), move |channel| {
+                            let mut values = colors_nodes.iter().map(|color| color[*channel].clamp(0.0, 1.0)).collect::<Vec<_>>();
+                            let samples = colors_fine.iter().map(|color| color[*channel].clamp(0.0, 1.0)).collect::<Vec<_>>();
+
+                            optimize_with(precision, interpolation, size, &mut values, &samples);
+
+                            values
+                        });
+
+                        *colors_output = (0..size.pow(3)).map(|index| Vector3([0, 1, 2].map(|channel| channels[channel % channels.len()][index]))).collect();
+
+                        phase_optimize.advance(1);
+                    }
+                }
+
+                for (index_output, colors_output) in colors_lut.into_iter().enumerate() {
+                    output.write_colors(index_output, &colors_output)?;
+                    start_write().advance(colors_output.len() as u64);
+                }
+            }
+
+            output.finish()?;
+        }
+
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {},
+        // The outputs are all closed by now, and dropping the search structure
+        // deletes its store, if any.
+        Err(error) if error.kind() == ErrorKind::Interrupted => {
+            drop(search);
+            cancelout(&paths_output);
+        },
+        Err(error) => failout(&paths_output, format!("Encountered an IO error: {}.", error)),
     }
 
-    threadpool.join();
+    for phase in [phase_lookup, phase_solve, phase_transform, phase_optimize, phase_write] {
+        if let Some(phase) = phase.into_inner() {
+            phase.finish();
+        }
+    }
+
+    // Report the accuracy that the tolerance was met with.
+    if let Some(error) = error {
+        let share = match statistic {
+            Statistic::Maximum => "All",
+            Statistic::Percentile95 => "95% of",
+        };
 
-    // Combine individual thread results into complete 3D LUTs.
-    for result in Arc::try_unwrap(arc_results).unwrap().into_inner().unwrap() {
-        for (index, mut result_output) in result.into_iter().enumerate() {
-            outputs[index].1.append(&mut result_output);
+        println!("{} adjacent secondary colors are within {:.3} \u{394}E of each other, sampling {} fractions per ink.", share, error, resolution);
+    }
+}
+
+/// Cancels the given token when the program is sent an interrupt signal. A
+/// second interrupt signal kills the program outright.
+#[cfg(unix)]
+fn handle_interrupts(cancellation: &Cancellation) {
+    extern "C" fn handle(_: libc::c_int) {
+        if let Some(cancellation) = CANCELLATION.get() {
+            cancellation.cancel();
+        }
+
+        // SAFETY: Resetting a signal's handler is async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
         }
     }
 
-    // Apply forward color transformations to the 3D LUTs that require it.
-    transformation_forward.transform_in_place(&mut outputs[0].1);
-    for index_output in (1..outputs.len()).step_by(2) {
-        transformation_forward.transform_in_place(&mut outputs[index_output].1);
+    let _ = CANCELLATION.set(cancellation.clone());
+
+    // SAFETY: The handler only does what's async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGINT, handle as extern "C" fn(libc::c_int) as libc::sighandler_t);
     }
+}
 
-    // Write the 3D LUT files.
-    for (file_output, colors_output) in outputs {
-        let mut output = BufWriter::new(file_output);
+/// Interrupt signals just kill the program outright elsewhere.
+#[cfg(not(unix))]
+fn handle_interrupts(_: &Cancellation) {}
 
-        // I mean, it's kinda like a try-catch block, right?
-        (|| {
-            writeln!(output, "LUT_3D_SIZE {}", size)?;
-            writeln!(output, "DOMAIN_MIN 0 0 0")?;
-            writeln!(output, "DOMAIN_MAX 1 1 1")?;
-    
-            for color in colors_output {
-                writeln!(output, "{} {} {}",
-                    color[0].clamp(0.0, 1.0),
-                    color[1].clamp(0.0, 1.0),
-                    color[2].clamp(0.0, 1.0)
-                )?;
+/// Returns the error that work abandoned on cancellation is reported with.
+fn interrupted() -> io::Error {
+    io::Error::new(ErrorKind::Interrupted, "Cancelled.")
+}
+
+/// Generates the origin 3D LUT colors of the slice of a 3D LUT of the given
+/// size with the given blue index, in their correct order.
+fn colors_slice(size: usize, index_blue: usize) -> Vec<Vector3> {
+    let component_blue = index_blue as f32 / (size - 1) as f32;
+
+    let mut colors_slice = Vec::with_capacity(size * size);
+    for index_green in 0..size {
+        let component_green = index_green as f32 / (size - 1) as f32;
+
+        for index_red in 0..size {
+            let component_red = index_red as f32 / (size - 1) as f32;
+
+            colors_slice.push(Vector3([component_red, component_green, component_blue]));
+        }
+    }
+
+    colors_slice
+}
+
+/// Returns the colors of each 3D LUT at the 3D LUT colors with the given ink
+/// fractions, or just those of the 3D LUT with the given index, mixing the
+/// given inks and moving the colors back into the profile's space with the
+/// given transformation across the given number of threads, which counts
+/// towards the given phase.
+fn colors_lut<T: Scalar>(
+    inks: &Inks<T>,
+    transformation: &Arc<Transformation<T>>,
+    components: &[Vec<f32>],
+    index_output: Option<usize>,
+    count_threads: usize,
+    phase: &Phase,
+) -> io::Result<Vec<Vec<Vector3<f64>>>> {
+    (0..1 + 2 * inks.primaries.len()).map(|index| {
+        if index_output.is_some_and(|index_output| index != index_output) {
+            return Ok(Vec::new());
+        }
+
+        let colors = components.iter().map(|components| {
+            match index {
+                0 => inks.mix(components),
+                index if index % 2 == 1 => {
+                    let fraction = T::from_f32(components[index / 2]);
+
+                    inks.primaries[index / 2] * fraction + inks.white * (T::one() - fraction)
+                },
+                index => {
+                    let fraction = T::from_f32(components[index / 2 - 1]);
+
+                    Vector3([fraction, fraction, fraction])
+                },
             }
+        }).collect::<Vec<_>>();
 
-            Ok(())
-        })().unwrap_or_else(|error: io::Error|
-            errorout(format!("Encountered an IO error: {}.", error))
-        );
+        // Move the colors back into the profile's space, except the masks.
+        let colors = if index % 2 == 1 || index == 0 {
+            parallel::transform_progress(count_threads, transformation, colors, phase).ok_or_else(interrupted)?
+        }
+        else {
+            colors
+        };
+
+        Ok(colors.into_iter().map(Vector3::cast).collect())
+    }).collect()
+}
+
+/// Adjusts the given node values of a 3D LUT of the given size to the given
+/// samples as `optimize::optimize` does, computing with the given precision.
+fn optimize_with(precision: Precision, interpolation: Interpolation, size: usize, values: &mut [f64], samples: &[f64]) {
+    match precision {
+        Precision::Single => {
+            let mut values_single = values.iter().map(|value| *value as f32).collect::<Vec<_>>();
+            let samples_single = samples.iter().map(|sample| *sample as f32).collect::<Vec<_>>();
+
+            optimize::optimize(interpolation, size, &mut values_single, &samples_single);
+
+            for (value, value_single) in values.iter_mut().zip(values_single) {
+                *value = value_single as f64;
+            }
+        },
+        Precision::Double => optimize::optimize(interpolation, size, values, samples),
     }
-}
\ No newline at end of file
+}
+
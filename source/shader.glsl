@@ -0,0 +1,111 @@
+// The nodes of the 3D LUTs that tetrahedral interpolation blends together at a
+// color, and their weights.
+struct SeparationTetrahedron {
+    ivec4 indices;
+    vec4 weights;
+};
+
+// Returns the index of the given node of the 3D LUTs, red fastest.
+int separation_index(ivec3 node) {
+    return (node.z * SEPARATION_SIZE + node.y) * SEPARATION_SIZE + node.x;
+}
+
+// Returns the tetrahedron containing the given color, clamped to the domain of
+// the 3D LUTs, out of the 6 that share the diagonal of its cell from its lowest
+// to its highest corner.
+SeparationTetrahedron separation_tetrahedron(vec3 color) {
+    vec3 position = clamp(color, 0.0, 1.0) * float(SEPARATION_SIZE - 1);
+    vec3 base = min(floor(position), vec3(float(SEPARATION_SIZE - 2)));
+    vec3 offset = position - base;
+
+    // Walk from the lowest corner to the highest, stepping along the axes in
+    // order of decreasing offset.
+    ivec3 corner1;
+    ivec3 corner2;
+    vec4 weights;
+    if (offset.x >= offset.y) {
+        if (offset.y >= offset.z) {
+            corner1 = ivec3(1, 0, 0);
+            corner2 = ivec3(1, 1, 0);
+            weights = vec4(1.0 - offset.x, offset.x - offset.y, offset.y - offset.z, offset.z);
+        }
+        else if (offset.x >= offset.z) {
+            corner1 = ivec3(1, 0, 0);
+            corner2 = ivec3(1, 0, 1);
+            weights = vec4(1.0 - offset.x, offset.x - offset.z, offset.z - offset.y, offset.y);
+        }
+        else {
+            corner1 = ivec3(0, 0, 1);
+            corner2 = ivec3(1, 0, 1);
+            weights = vec4(1.0 - offset.z, offset.z - offset.x, offset.x - offset.y, offset.y);
+        }
+    }
+    else {
+        if (offset.z >= offset.y) {
+            corner1 = ivec3(0, 0, 1);
+            corner2 = ivec3(0, 1, 1);
+            weights = vec4(1.0 - offset.z, offset.z - offset.y, offset.y - offset.x, offset.x);
+        }
+        else if (offset.z >= offset.x) {
+            corner1 = ivec3(0, 1, 0);
+            corner2 = ivec3(0, 1, 1);
+            weights = vec4(1.0 - offset.y, offset.y - offset.z, offset.z - offset.x, offset.x);
+        }
+        else {
+            corner1 = ivec3(0, 1, 0);
+            corner2 = ivec3(1, 1, 0);
+            weights = vec4(1.0 - offset.y, offset.y - offset.x, offset.x - offset.z, offset.z);
+        }
+    }
+
+    ivec3 node = ivec3(base);
+
+    return SeparationTetrahedron(
+        ivec4(separation_index(node), separation_index(node + corner1), separation_index(node + corner2), separation_index(node + ivec3(1))),
+        weights
+    );
+}
+
+// Returns the color of the composite 3D LUT at the given color.
+vec3 separation_composite(vec3 color) {
+    SeparationTetrahedron tetrahedron = separation_tetrahedron(color);
+
+    return tetrahedron.weights.x * SEPARATION_COMPOSITE[tetrahedron.indices.x]
+        + tetrahedron.weights.y * SEPARATION_COMPOSITE[tetrahedron.indices.y]
+        + tetrahedron.weights.z * SEPARATION_COMPOSITE[tetrahedron.indices.z]
+        + tetrahedron.weights.w * SEPARATION_COMPOSITE[tetrahedron.indices.w];
+}
+
+// Returns the coverage fraction of the given ink within the given tetrahedron.
+float separation_fraction(int ink, SeparationTetrahedron tetrahedron) {
+    int offset = ink * SEPARATION_SIZE * SEPARATION_SIZE * SEPARATION_SIZE;
+
+    return dot(tetrahedron.weights, vec4(
+        SEPARATION_MASKS[offset + tetrahedron.indices.x],
+        SEPARATION_MASKS[offset + tetrahedron.indices.y],
+        SEPARATION_MASKS[offset + tetrahedron.indices.z],
+        SEPARATION_MASKS[offset + tetrahedron.indices.w]
+    ));
+}
+
+// Returns the coverage fraction of the given ink at the given color, as its
+// mask 3D LUT has it.
+float separation_mask(int ink, vec3 color) {
+    return separation_fraction(ink, separation_tetrahedron(color));
+}
+
+// Returns the color of the inks set by separation_inks mixed over the paper set
+// by separation_paper at the given color, mixing subtractively as the color
+// separation does, though in RGB rather than XYZ space.
+vec3 separation_mix(vec3 color) {
+    SeparationTetrahedron tetrahedron = separation_tetrahedron(color);
+
+    vec3 mixture = separation_paper;
+    for (int ink = 0; ink < SEPARATION_COUNT_INKS; ink++) {
+        float fraction = separation_fraction(ink, tetrahedron);
+
+        mixture *= mix(separation_paper, separation_inks[ink], fraction) / separation_paper;
+    }
+
+    return mixture;
+}
@@ -0,0 +1,335 @@
+use std::io;
+use std::io::Write;
+
+use crate::lut::Metadata;
+use crate::lut::SeparationWriter;
+use crate::vector::Vector3;
+
+/// The largest 3D LUT size of shader modules, whose 3D LUTs are array literals
+/// with an element for each node, which shader compilers are slow to take in
+/// the thousands, let alone the hundreds of thousands.
+pub const SIZE_MAX: usize = 33;
+
+/// A shading language that shader modules can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    /// The OpenGL Shading Language.
+    Glsl,
+    /// The High-Level Shading Language of Direct3D.
+    Hlsl,
+    /// The WebGPU Shading Language.
+    Wgsl,
+}
+
+impl Language {
+    /// Returns the source of the functions that sample the 3D LUTs of shader
+    /// modules in this language.
+    fn functions(&self) -> &'static str {
+        match self {
+            Language::Glsl => include_str!("shader.glsl"),
+            Language::Hlsl => include_str!("shader.hlsl"),
+            Language::Wgsl => include_str!("shader.wgsl"),
+        }
+    }
+
+    /// Returns the given color as a vector literal in this language.
+    fn vector(&self, color: Vector3<f64>) -> String {
+        let [red, green, blue] = color.0.map(literal);
+
+        match self {
+            Language::Glsl => format!("vec3({}, {}, {})", red, green, blue),
+            Language::Hlsl => format!("float3({}, {}, {})", red, green, blue),
+            Language::Wgsl => format!("vec3<f32>({}, {}, {})", red, green, blue),
+        }
+    }
+}
+
+/// A writer of the 3D LUTs of a color separation as a fragment shader module
+/// that embeds the composite and mask 3D LUTs as arrays, along with functions
+/// that sample them with tetrahedral interpolation, and uniforms for the colors
+/// of the inks and paper that the masks can be mixed with instead.
+///
+/// Of the 3D LUTs of the color separation, only the composite and masks are
+/// kept. The module is written once they're finished.
+pub struct Shader<W: Write> {
+    output: W,
+    language: Language,
+    metadata: Metadata,
+    inks: Vec<Vector3<f64>>,
+    size: usize,
+    composite: Vec<Vector3<f64>>,
+    masks: Vec<Vec<f64>>,
+}
+
+impl<W: Write> Shader<W> {
+    /// Returns a writer to the given output of a shader module in the given
+    /// language, recording the given metadata, whose inks default to the given
+    /// colors, in the profile's space and between 0 and 1.
+    pub fn new(output: W, language: Language, metadata: Metadata, inks: Vec<Vector3<f64>>) -> Self {
+        Self {
+            output,
+            language,
+            metadata,
+            masks: vec![Vec::new(); inks.len()],
+            inks,
+            size: 0,
+            composite: Vec::new(),
+        }
+    }
+
+    /// Writes the shader module to the output.
+    fn write(&mut self) -> io::Result<()> {
+        let language = self.language;
+        let size = self.size;
+        let count_inks = self.inks.len();
+        let count_nodes = size.pow(3);
+
+        writeln!(self.output, "// {}", self.metadata.name)?;
+        writeln!(self.output, "// {}", self.metadata.description)?;
+        for (key, value) in self.metadata.info.iter() {
+            writeln!(self.output, "// {}: {}", key, value)?;
+        }
+        writeln!(self.output)?;
+
+        let inks = self.inks.iter().map(|ink| language.vector(*ink)).collect::<Vec<_>>().join(", ");
+        let paper = language.vector(Vector3([1.0, 1.0, 1.0]));
+
+        match language {
+            Language::Glsl => {
+                writeln!(self.output, "const int SEPARATION_SIZE = {};", size)?;
+                writeln!(self.output, "const int SEPARATION_COUNT_INKS = {};", count_inks)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper of the color separation, which the")?;
+                writeln!(self.output, "// uniforms should start out as.")?;
+                writeln!(self.output, "const vec3 SEPARATION_INKS_DEFAULT[{}] = vec3[]({});", count_inks, inks)?;
+                writeln!(self.output, "const vec3 SEPARATION_PAPER_DEFAULT = {};", paper)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper that separation_mix mixes.")?;
+                writeln!(self.output, "uniform vec3 separation_inks[SEPARATION_COUNT_INKS];")?;
+                writeln!(self.output, "uniform vec3 separation_paper;")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "const vec3 SEPARATION_COMPOSITE[{}] = vec3[](", count_nodes)?;
+            },
+            Language::Hlsl => {
+                writeln!(self.output, "static const int SEPARATION_SIZE = {};", size)?;
+                writeln!(self.output, "static const int SEPARATION_COUNT_INKS = {};", count_inks)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper of the color separation, which the")?;
+                writeln!(self.output, "// uniforms should start out as.")?;
+                writeln!(self.output, "static const float3 SEPARATION_INKS_DEFAULT[{}] = {{ {} }};", count_inks, inks)?;
+                writeln!(self.output, "static const float3 SEPARATION_PAPER_DEFAULT = {};", paper)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper that separation_mix mixes.")?;
+                writeln!(self.output, "cbuffer Separation {{")?;
+                writeln!(self.output, "    float3 separation_inks[SEPARATION_COUNT_INKS];")?;
+                writeln!(self.output, "    float3 separation_paper;")?;
+                writeln!(self.output, "}};")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "static const float3 SEPARATION_COMPOSITE[{}] = {{", count_nodes)?;
+            },
+            Language::Wgsl => {
+                writeln!(self.output, "const SEPARATION_SIZE: i32 = {};", size)?;
+                writeln!(self.output, "const SEPARATION_COUNT_INKS: i32 = {};", count_inks)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper of the color separation, which the")?;
+                writeln!(self.output, "// uniforms should start out as.")?;
+                writeln!(self.output, "const SEPARATION_INKS_DEFAULT = array<vec3<f32>, {}>({});", count_inks, inks)?;
+                writeln!(self.output, "const SEPARATION_PAPER_DEFAULT = {};", paper)?;
+                writeln!(self.output)?;
+                writeln!(self.output, "// The colors of the inks and paper that separation_mix mixes, of which")?;
+                writeln!(self.output, "// only the red, green and blue components are used.")?;
+                writeln!(self.output, "struct Separation {{")?;
+                writeln!(self.output, "    inks: array<vec4<f32>, {}>,", count_inks)?;
+                writeln!(self.output, "    paper: vec4<f32>,")?;
+                writeln!(self.output, "}}")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "@group(0) @binding(0) var<uniform> separation: Separation;")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "var<private> SEPARATION_COMPOSITE: array<vec3<f32>, {}> = array<vec3<f32>, {}>(", count_nodes, count_nodes)?;
+            },
+        }
+
+        for (index, color) in self.composite.iter().enumerate() {
+            let color = Vector3(color.0.map(|component| component.clamp(0.0, 1.0)));
+
+            writeln!(self.output, "    {}{}", language.vector(color), if index + 1 < count_nodes { "," } else { "" })?;
+        }
+
+        // The masks follow one another in a single array.
+        match language {
+            Language::Glsl => {
+                writeln!(self.output, ");")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "const float SEPARATION_MASKS[{}] = float[](", count_inks * count_nodes)?;
+            },
+            Language::Hlsl => {
+                writeln!(self.output, "}};")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "static const float SEPARATION_MASKS[{}] = {{", count_inks * count_nodes)?;
+            },
+            Language::Wgsl => {
+                writeln!(self.output, ");")?;
+                writeln!(self.output)?;
+                writeln!(self.output, "var<private> SEPARATION_MASKS: array<f32, {}> = array<f32, {}>(", count_inks * count_nodes, count_inks * count_nodes)?;
+            },
+        }
+
+        for (index, fraction) in self.masks.iter().flatten().enumerate() {
+            writeln!(self.output, "    {}{}", literal(fraction.clamp(0.0, 1.0)), if index + 1 < count_inks * count_nodes { "," } else { "" })?;
+        }
+
+        match language {
+            Language::Glsl | Language::Wgsl => writeln!(self.output, ");")?,
+            Language::Hlsl => writeln!(self.output, "}};")?,
+        }
+        writeln!(self.output)?;
+
+        write!(self.output, "{}", language.functions())?;
+
+        self.composite = Vec::new();
+        for mask in self.masks.iter_mut() {
+            *mask = Vec::new();
+        }
+
+        self.output.flush()
+    }
+}
+
+impl<W: Write> SeparationWriter for Shader<W> {
+    fn begin(&mut self, size: usize) -> io::Result<()> {
+        self.size = size;
+        self.composite = Vec::with_capacity(size.pow(3));
+        for mask in self.masks.iter_mut() {
+            *mask = Vec::with_capacity(size.pow(3));
+        }
+
+        Ok(())
+    }
+
+    fn write_colors(&mut self, index: usize, colors: &[Vector3<f64>]) -> io::Result<()> {
+        // Masks are grays, so any component of theirs is the fraction.
+        match index {
+            0 => self.composite.extend_from_slice(colors),
+            index if index % 2 == 0 => self.masks[index / 2 - 1].extend(colors.iter().map(|color| color[0])),
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.write()
+    }
+}
+
+/// Returns the given value as a floating-point literal, which every shading
+/// language takes alike.
+fn literal(value: f64) -> String {
+    format!("{:?}", value as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Returns the shader module in the given language of a color separation
+    /// of a single ink, whose 3D LUTs of size 2 are each made of colors of a
+    /// different tenth, asserting that it's only written once the 3D LUTs are
+    /// finished.
+    fn module(language: Language) -> String {
+        let metadata = Metadata {
+            name: String::from("output"),
+            description: String::from("A color separation"),
+            info: vec![(String::from("Profile"), String::from("sRGB"))],
+            mask: false,
+            profile: Vec::new(),
+        };
+
+        // Write to a shared buffer to look at the module afterwards.
+        #[derive(Clone, Default)]
+        struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Buffer {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(data)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let mut writer = Shader::new(buffer.clone(), language, metadata, vec![Vector3([1.0, 0.0, 0.0])]);
+
+        writer.begin(2).unwrap();
+        for index in 0..3 {
+            let colors = (0..8).map(|node| Vector3([(10 * index + node) as f64 / 100.0; 3])).collect::<Vec<_>>();
+
+            writer.write_colors(index, &colors[..4]).unwrap();
+            writer.write_colors(index, &colors[4..]).unwrap();
+        }
+
+        assert!(buffer.0.borrow().is_empty());
+        writer.finish().unwrap();
+
+        let module = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+
+        // The primary 3D LUT is left out.
+        assert!(module.starts_with("// output\n// A color separation\n// Profile: sRGB\n"));
+        assert!(!module.contains("0.1,"));
+        assert!(module.ends_with(language.functions()));
+
+        module
+    }
+
+    /// Tests that GLSL modules hold the composite and masks as arrays, and
+    /// the inks and paper as uniforms.
+    #[test]
+    fn test_shader_glsl() {
+        let module = module(Language::Glsl);
+
+        assert!(module.contains("const int SEPARATION_SIZE = 2;"));
+        assert!(module.contains("const vec3 SEPARATION_INKS_DEFAULT[1] = vec3[](vec3(1.0, 0.0, 0.0));"));
+        assert!(module.contains("uniform vec3 separation_inks[SEPARATION_COUNT_INKS];\nuniform vec3 separation_paper;\n"));
+        assert!(module.contains("const vec3 SEPARATION_COMPOSITE[8] = vec3[](\n    vec3(0.0, 0.0, 0.0),\n"));
+        assert!(module.contains("    vec3(0.07, 0.07, 0.07)\n);"));
+        assert!(module.contains("const float SEPARATION_MASKS[8] = float[](\n    0.2,\n"));
+        assert!(module.contains("    0.27\n);"));
+    }
+
+    /// Tests that HLSL modules hold the composite and masks as arrays, and
+    /// the inks and paper in a constant buffer.
+    #[test]
+    fn test_shader_hlsl() {
+        let module = module(Language::Hlsl);
+
+        assert!(module.contains("static const int SEPARATION_SIZE = 2;"));
+        assert!(module.contains("static const float3 SEPARATION_INKS_DEFAULT[1] = { float3(1.0, 0.0, 0.0) };"));
+        assert!(module.contains("cbuffer Separation {\n    float3 separation_inks[SEPARATION_COUNT_INKS];\n    float3 separation_paper;\n};\n"));
+        assert!(module.contains("static const float3 SEPARATION_COMPOSITE[8] = {\n    float3(0.0, 0.0, 0.0),\n"));
+        assert!(module.contains("    float3(0.07, 0.07, 0.07)\n};"));
+        assert!(module.contains("static const float SEPARATION_MASKS[8] = {\n    0.2,\n"));
+        assert!(module.contains("    0.27\n};"));
+    }
+
+    /// Tests that WGSL modules hold the composite and masks as arrays, and the
+    /// inks and paper in a uniform buffer, as 4-component vectors so that the
+    /// array of inks has the 16-byte stride that uniform buffers call for.
+    #[test]
+    fn test_shader_wgsl() {
+        let module = module(Language::Wgsl);
+
+        assert!(module.contains("const SEPARATION_SIZE: i32 = 2;"));
+        assert!(module.contains("const SEPARATION_INKS_DEFAULT = array<vec3<f32>, 1>(vec3<f32>(1.0, 0.0, 0.0));"));
+        assert!(module.contains("struct Separation {\n    inks: array<vec4<f32>, 1>,\n    paper: vec4<f32>,\n}\n"));
+        assert!(module.contains("@group(0) @binding(0) var<uniform> separation: Separation;"));
+        assert!(module.contains("separation.inks[ink].rgb"));
+        assert!(module.contains("var<private> SEPARATION_COMPOSITE: array<vec3<f32>, 8> = array<vec3<f32>, 8>(\n    vec3<f32>(0.0, 0.0, 0.0),\n"));
+        assert!(module.contains("    vec3<f32>(0.07, 0.07, 0.07)\n);"));
+        assert!(module.contains("var<private> SEPARATION_MASKS: array<f32, 8> = array<f32, 8>(\n    0.2,\n"));
+        assert!(module.contains("    0.27\n);"));
+    }
+}
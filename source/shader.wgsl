@@ -0,0 +1,111 @@
+// The nodes of the 3D LUTs that tetrahedral interpolation blends together at a
+// color, and their weights.
+struct SeparationTetrahedron {
+    indices: vec4<i32>,
+    weights: vec4<f32>,
+}
+
+// Returns the index of the given node of the 3D LUTs, red fastest.
+fn separation_index(node: vec3<i32>) -> i32 {
+    return (node.z * SEPARATION_SIZE + node.y) * SEPARATION_SIZE + node.x;
+}
+
+// Returns the tetrahedron containing the given color, clamped to the domain of
+// the 3D LUTs, out of the 6 that share the diagonal of its cell from its lowest
+// to its highest corner.
+fn separation_tetrahedron(color: vec3<f32>) -> SeparationTetrahedron {
+    let position = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)) * f32(SEPARATION_SIZE - 1);
+    let base = min(floor(position), vec3<f32>(f32(SEPARATION_SIZE - 2)));
+    let offset = position - base;
+
+    // Walk from the lowest corner to the highest, stepping along the axes in
+    // order of decreasing offset.
+    var corner1: vec3<i32>;
+    var corner2: vec3<i32>;
+    var weights: vec4<f32>;
+    if (offset.x >= offset.y) {
+        if (offset.y >= offset.z) {
+            corner1 = vec3<i32>(1, 0, 0);
+            corner2 = vec3<i32>(1, 1, 0);
+            weights = vec4<f32>(1.0 - offset.x, offset.x - offset.y, offset.y - offset.z, offset.z);
+        }
+        else if (offset.x >= offset.z) {
+            corner1 = vec3<i32>(1, 0, 0);
+            corner2 = vec3<i32>(1, 0, 1);
+            weights = vec4<f32>(1.0 - offset.x, offset.x - offset.z, offset.z - offset.y, offset.y);
+        }
+        else {
+            corner1 = vec3<i32>(0, 0, 1);
+            corner2 = vec3<i32>(1, 0, 1);
+            weights = vec4<f32>(1.0 - offset.z, offset.z - offset.x, offset.x - offset.y, offset.y);
+        }
+    }
+    else {
+        if (offset.z >= offset.y) {
+            corner1 = vec3<i32>(0, 0, 1);
+            corner2 = vec3<i32>(0, 1, 1);
+            weights = vec4<f32>(1.0 - offset.z, offset.z - offset.y, offset.y - offset.x, offset.x);
+        }
+        else if (offset.z >= offset.x) {
+            corner1 = vec3<i32>(0, 1, 0);
+            corner2 = vec3<i32>(0, 1, 1);
+            weights = vec4<f32>(1.0 - offset.y, offset.y - offset.z, offset.z - offset.x, offset.x);
+        }
+        else {
+            corner1 = vec3<i32>(0, 1, 0);
+            corner2 = vec3<i32>(1, 1, 0);
+            weights = vec4<f32>(1.0 - offset.y, offset.y - offset.x, offset.x - offset.z, offset.z);
+        }
+    }
+
+    let node = vec3<i32>(base);
+
+    return SeparationTetrahedron(
+        vec4<i32>(separation_index(node), separation_index(node + corner1), separation_index(node + corner2), separation_index(node + vec3<i32>(1))),
+        weights
+    );
+}
+
+// Returns the color of the composite 3D LUT at the given color.
+fn separation_composite(color: vec3<f32>) -> vec3<f32> {
+    let tetrahedron = separation_tetrahedron(color);
+
+    return tetrahedron.weights.x * SEPARATION_COMPOSITE[tetrahedron.indices.x]
+        + tetrahedron.weights.y * SEPARATION_COMPOSITE[tetrahedron.indices.y]
+        + tetrahedron.weights.z * SEPARATION_COMPOSITE[tetrahedron.indices.z]
+        + tetrahedron.weights.w * SEPARATION_COMPOSITE[tetrahedron.indices.w];
+}
+
+// Returns the coverage fraction of the given ink within the given tetrahedron.
+fn separation_fraction(ink: i32, tetrahedron: SeparationTetrahedron) -> f32 {
+    let offset = ink * SEPARATION_SIZE * SEPARATION_SIZE * SEPARATION_SIZE;
+
+    return dot(tetrahedron.weights, vec4<f32>(
+        SEPARATION_MASKS[offset + tetrahedron.indices.x],
+        SEPARATION_MASKS[offset + tetrahedron.indices.y],
+        SEPARATION_MASKS[offset + tetrahedron.indices.z],
+        SEPARATION_MASKS[offset + tetrahedron.indices.w]
+    ));
+}
+
+// Returns the coverage fraction of the given ink at the given color, as its
+// mask 3D LUT has it.
+fn separation_mask(ink: i32, color: vec3<f32>) -> f32 {
+    return separation_fraction(ink, separation_tetrahedron(color));
+}
+
+// Returns the color of the inks set by separation.inks mixed over the paper set
+// by separation.paper at the given color, mixing subtractively as the color
+// separation does, though in RGB rather than XYZ space.
+fn separation_mix(color: vec3<f32>) -> vec3<f32> {
+    let tetrahedron = separation_tetrahedron(color);
+
+    var mixture = separation.paper.rgb;
+    for (var ink = 0; ink < SEPARATION_COUNT_INKS; ink++) {
+        let fraction = separation_fraction(ink, tetrahedron);
+
+        mixture *= mix(separation.paper.rgb, separation.inks[ink].rgb, fraction) / separation.paper.rgb;
+    }
+
+    return mixture;
+}
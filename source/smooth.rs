@@ -0,0 +1,140 @@
+use crate::inks::Inks;
+use crate::mapping;
+use crate::secondaries::Lattice;
+
+/// The number of nearest secondary colors considered as candidate recipes for
+/// each 3D LUT color.
+pub const COUNT_CANDIDATES: usize = 16;
+
+/// The maximum number of passes smoothing makes over a 3D LUT.
+const ITERATIONS_MAX: usize = 16;
+
+/// Returns the ink fractions of the secondary colors with the given indices on
+/// the given lattice, nearest first, that are within the given CIE76 color
+/// difference of the nearest one, laid end to end. The nearest one is always
+/// kept.
+pub fn candidates(inks: &Inks, lattice: Lattice, indices: &[u64], tolerance: f32) -> Vec<f32> {
+    let mut candidates = Vec::with_capacity(indices.len() * lattice.count_primaries);
+
+    let lab_nearest = mapping::lab(inks.mix(&lattice.fractions(indices[0])), inks.white);
+
+    for index in indices {
+        let fractions = lattice.fractions(*index);
+        let difference = mapping::lab(inks.mix(&fractions), inks.white) - lab_nearest;
+
+        if difference[0].powi(2) + difference[1].powi(2) + difference[2].powi(2) <= tolerance.powi(2) {
+            candidates.extend(fractions);
+        }
+    }
+
+    candidates
+}
+
+/// Chooses a recipe for each color of a 3D LUT of the given size, in its usual
+/// order, among the given candidate recipes of the given number of inks, so
+/// that the recipes vary smoothly from color to color. Returns the ink
+/// fractions of the chosen recipes.
+///
+/// Every color starts out with its first candidate. Each pass then switches
+/// every color, one at a time, to the candidate with the least squared
+/// difference in fractions from the current recipes of its (up to) six
+/// adjacent colors, until a pass switches nothing.
+pub fn smooth(size: usize, count_primaries: usize, candidates: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let mut choices = vec![0; candidates.len()];
+
+    // Returns the fractions of the given candidate of the given color.
+    let candidate = |index: usize, choice: usize| -> &[f32] {
+        &candidates[index][choice * count_primaries..(choice + 1) * count_primaries]
+    };
+
+    for _ in 0..ITERATIONS_MAX {
+        let mut changed = false;
+
+        for index in 0..candidates.len() {
+            let count_candidates = candidates[index].len() / count_primaries;
+            if count_candidates <= 1 {
+                continue;
+            }
+
+            let coordinates = [index % size, index / size % size, index / (size * size)];
+            let strides = [1, size, size * size];
+
+            let mut neighbors = Vec::with_capacity(6);
+            for axis in 0..3 {
+                if coordinates[axis] > 0 {
+                    neighbors.push(index - strides[axis]);
+                }
+                if coordinates[axis] + 1 < size {
+                    neighbors.push(index + strides[axis]);
+                }
+            }
+
+            let cost = |choice: usize| -> f32 {
+                let fractions = candidate(index, choice);
+
+                neighbors.iter().map(|neighbor| {
+                    let fractions_neighbor = candidate(*neighbor, choices[*neighbor]);
+
+                    fractions.iter().zip(fractions_neighbor).map(|(a, b)| (a - b).powi(2)).sum::<f32>()
+                }).sum()
+            };
+
+            // Ties go to the nearer candidate.
+            let mut choice_best = 0;
+            let mut cost_best = cost(0);
+            for choice in 1..count_candidates {
+                let cost = cost(choice);
+
+                if cost < cost_best {
+                    choice_best = choice;
+                    cost_best = cost;
+                }
+            }
+
+            if choice_best != choices[index] {
+                choices[index] = choice_best;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    choices.into_iter().enumerate().map(|(index, choice)| candidate(index, choice).to_vec()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    /// Tests that smoothing picks out the candidates that agree with their
+    /// neighbors over the ones that don't.
+    #[test]
+    fn test_smooth() {
+        let size = 3;
+
+        // Every color has the recipe 0.5 available, but half of them also have
+        // a nearer but wildly different recipe.
+        let candidates = (0..size * size * size).map(|index| {
+            if index % 2 == 0 { vec![(index % 4) as f32 / 3.0, 0.5] } else { vec![0.5] }
+        }).collect::<Vec<_>>();
+
+        let result = smooth(size, 1, &candidates);
+
+        assert!(result.iter().all(|fractions| fractions == &[0.5]));
+    }
+
+    /// Tests that only candidates within the tolerance are kept.
+    #[test]
+    fn test_candidates() {
+        let inks = testing::inks(1, f32::INFINITY);
+        let lattice = Lattice::new(101, 1).unwrap();
+
+        assert_eq!(vec![0.5, 0.51, 0.49], candidates(&inks, lattice, &[50, 51, 49, 80], 3.0));
+        assert_eq!(vec![0.5], candidates(&inks, lattice, &[50, 51, 49, 80], 0.0));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::inks::Inks;
+use crate::mapping::Mapping;
+use crate::vector::Vector3;
+
+/// The maximum number of iterations the solver will perform per color.
+const ITERATIONS_MAX: usize = 64;
+
+/// The step size used to approximate the derivatives of the mixing model.
+const STEP_DERIVATIVE: f32 = 1e-3;
+
+/// The initial damping factor of the Levenberg-Marquardt iteration.
+const DAMPING_INITIAL: f32 = 1e-3;
+
+/// The number of times the damping factor may be raised in search of a step
+/// that improves the solution before the solver gives up.
+const DAMPING_ATTEMPTS: usize = 12;
+
+/// Solves for the continuous coverage fractions of the given inks whose mixture
+/// is nearest to the given target point under the given mapping, starting the
+/// search from the given seed fractions.
+///
+/// The solver is a projected Levenberg-Marquardt iteration: every step is
+/// projected back onto the fractions that lie between 0 and 1 and respect the
+/// ink limit, so the solution always does too. The target must be in the
+/// mapping's space.
+pub fn solve(inks: &Inks, mapping: &Mapping, target: Vector3, seed: &[f32]) -> Vec<f32> {
+    let count = seed.len();
+
+    // Returns the residual between the mixture of the given fractions and the
+    // target.
+    let residual = |fractions: &[f32]| -> Vector3 {
+        mapping.residual(mapping.point(inks.mix(fractions), inks.white), target)
+    };
+    let cost = |residual: Vector3| -> f32 {
+        residual[0].powi(2) + residual[1].powi(2) + residual[2].powi(2)
+    };
+
+    let mut fractions = seed.to_vec();
+    inks.constrain(&mut fractions);
+
+    let mut residual_current = residual(&fractions);
+    let mut cost_current = cost(residual_current);
+    let mut damping = DAMPING_INITIAL;
+
+    let mut jacobian = vec![Vector3([0.0, 0.0, 0.0]); count];
+    let mut normal = vec![0.0; count * count];
+    let mut gradient = vec![0.0; count];
+    let mut candidate = vec![0.0; count];
+
+    for _ in 0..ITERATIONS_MAX {
+        if cost_current == 0.0 {
+            break;
+        }
+
+        // Approximate the Jacobian by finite differences, stepping inwards so
+        // as to never evaluate the model outside of the unit hypercube.
+        for index in 0..count {
+            let step = if fractions[index] + STEP_DERIVATIVE > 1.0 { -STEP_DERIVATIVE } else { STEP_DERIVATIVE };
+
+            candidate.copy_from_slice(&fractions);
+            candidate[index] += step;
+
+            jacobian[index] = (residual(&candidate) - residual_current) / step;
+        }
+
+        for row in 0..count {
+            for column in 0..count {
+                normal[row * count + column] = dot(jacobian[row], jacobian[column]);
+            }
+
+            gradient[row] = -dot(jacobian[row], residual_current);
+        }
+
+        let mut improved = false;
+
+        for _ in 0..DAMPING_ATTEMPTS {
+            let mut system = normal.clone();
+            for index in 0..count {
+                system[index * count + index] += damping * (normal[index * count + index] + f32::EPSILON);
+            }
+
+            let step = match solve_linear(&mut system, gradient.clone()) {
+                Some(step) => step,
+                None => {
+                    damping *= 10.0;
+                    continue;
+                },
+            };
+
+            for index in 0..count {
+                candidate[index] = fractions[index] + step[index];
+            }
+            inks.constrain(&mut candidate);
+
+            let residual_candidate = residual(&candidate);
+            let cost_candidate = cost(residual_candidate);
+
+            if cost_candidate < cost_current {
+                improved = cost_current - cost_candidate > 1e-6 * cost_current;
+
+                fractions.copy_from_slice(&candidate);
+                residual_current = residual_candidate;
+                cost_current = cost_candidate;
+                damping = (damping / 10.0).max(f32::EPSILON);
+
+                break;
+            }
+
+            damping *= 10.0;
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    fractions
+}
+
+/// Returns the dot product of the given vectors.
+fn dot(vector_a: Vector3, vector_b: Vector3) -> f32 {
+    vector_a[0] * vector_b[0] + vector_a[1] * vector_b[1] + vector_a[2] * vector_b[2]
+}
+
+/// Solves the given square linear system, stored in row-major order, by
+/// Gaussian elimination with partial pivoting. Returns `None` if the system is
+/// singular.
+fn solve_linear(matrix: &mut [f32], mut vector: Vec<f32>) -> Option<Vec<f32>> {
+    let count = vector.len();
+
+    for column in 0..count {
+        let pivot = (column..count).max_by(|a, b|
+            matrix[a * count + column].abs().total_cmp(&matrix[b * count + column].abs())
+        ).unwrap();
+
+        if matrix[pivot * count + column].abs() <= f32::MIN_POSITIVE {
+            return None;
+        }
+
+        if pivot != column {
+            for index in 0..count {
+                matrix.swap(pivot * count + index, column * count + index);
+            }
+            vector.swap(pivot, column);
+        }
+
+        for row in column + 1..count {
+            let factor = matrix[row * count + column] / matrix[column * count + column];
+
+            for index in column..count {
+                matrix[row * count + index] -= factor * matrix[column * count + index];
+            }
+            vector[row] -= factor * vector[column];
+        }
+    }
+
+    for row in (0..count).rev() {
+        let mut value = vector[row];
+        for index in row + 1..count {
+            value -= matrix[row * count + index] * vector[index];
+        }
+
+        vector[row] = value / matrix[row * count + row];
+    }
+
+    Some(vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mapping::WEIGHTS_LAB;
+    use crate::testing;
+
+    /// Tests that the solver recovers the fractions of a reachable mixture from
+    /// a poor seed.
+    #[test]
+    fn test_solve_reachable() {
+        let inks = testing::inks(3, f32::INFINITY);
+
+        for mapping in [Mapping::Colorimetric, Mapping::Weighted(WEIGHTS_LAB)] {
+            let reference = [0.3, 0.6, 0.45];
+            let target = mapping.point(inks.mix(&reference), inks.white);
+
+            let result = solve(&inks, &mapping, target, &[0.5, 0.5, 0.5]);
+
+            for index in 0..3 {
+                assert!((reference[index] - result[index]).abs() <= 1e-2,
+                    "{:?}: {:?} !~= {:?}",
+                    mapping,
+                    reference,
+                    result
+                );
+            }
+        }
+    }
+
+    /// Tests that the solver respects the ink limit.
+    #[test]
+    fn test_solve_limit() {
+        let inks = testing::inks(3, 1.0);
+        let mapping = Mapping::Colorimetric;
+
+        let target = mapping.point(inks.mix(&[1.0, 1.0, 1.0]), inks.white);
+
+        let result = solve(&inks, &mapping, target, &[0.0, 0.0, 0.0]);
+
+        assert!(result.iter().sum::<f32>() <= 1.0 + 1e-5);
+        assert!(result.iter().all(|fraction| (0.0..=1.0).contains(fraction)));
+    }
+
+    #[test]
+    fn test_solve_linear() {
+        let mut matrix = [2.0, 1.0, 1.0, 3.0];
+
+        let result = solve_linear(&mut matrix, vec![3.0, 5.0]).unwrap();
+
+        assert!((result[0] - 0.8).abs() <= 1e-6);
+        assert!((result[1] - 1.4).abs() <= 1e-6);
+    }
+}
@@ -0,0 +1,393 @@
+use memmap2::Mmap;
+use memmap2::MmapMut;
+use std::fs::remove_file;
+use std::fs::rename;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::mapping::Mapping;
+use crate::search::Grid;
+use crate::search::Search;
+use crate::secondaries::Lattice;
+use crate::vector::Vector3;
+
+/// The bytes a store's file begins with, which double as the version of its
+/// layout.
+const MAGIC: &[u8; 8] = b"SEPSTOR1";
+
+/// The size, in bytes, of each record of a store: the coordinates of a
+/// secondary color followed by its index.
+const SIZE_RECORD: usize = 20;
+
+/// The size, in bytes, of the header of a store's file: the magic bytes, the
+/// lattice's resolution and number of inks, the number of records, the number
+/// of cells along each axis of the grid, and the grid's minimum and cell size.
+const SIZE_HEADER: usize = 64;
+
+/// A store of secondary colors kept in a memory-mapped file rather than in
+/// memory, so that far more of them can be searched than would fit in memory.
+///
+/// The records in the file are bucketed into the cells of a uniform grid over
+/// the mapping's space, so that a search only ever touches the cells around
+/// the point being searched for. The file holds everything needed to search
+/// it, so a store can be reopened by later runs of the program. Unless it is
+/// persisted, the file is deleted when the store is dropped.
+pub struct Store {
+    path: PathBuf,
+    map: Mmap,
+    /// The lattice that the indices of the secondary colors refer to.
+    pub lattice: Lattice,
+    grid: Grid,
+    /// The offset, in bytes, of the first record in the file.
+    start: usize,
+    persistent: bool,
+}
+
+impl Store {
+    /// Creates a store at the given path from the secondary colors that the
+    /// given function hands to its argument as their coordinates in the
+    /// mapping's space and their indices on the given lattice. The function
+    /// must hand over exactly the given number of secondary colors, or fewer if
+    /// it's cancelled, in which case an `Interrupted` error is returned.
+    pub fn create(path: &Path, lattice: Lattice, count: usize, function: impl FnOnce(&mut dyn FnMut(Vector3, u64))) -> io::Result<Self> {
+        // Write the records unsorted to a scratch file first, noting the
+        // bounds of their coordinates along the way.
+        let mut path_scratch = path.as_os_str().to_os_string();
+        path_scratch.push(".unsorted");
+        let path_scratch = PathBuf::from(path_scratch);
+
+        let mut map_scratch = map_new(&path_scratch, count * SIZE_RECORD)?;
+
+        let mut minimum = Vector3([f32::INFINITY; 3]);
+        let mut maximum = Vector3([f32::NEG_INFINITY; 3]);
+        let mut written = 0;
+
+        function(&mut |point, index| {
+            assert!(written < count, "More secondary colors than expected.");
+
+            for axis in 0..3 {
+                minimum[axis] = minimum[axis].min(point[axis]);
+                maximum[axis] = maximum[axis].max(point[axis]);
+            }
+
+            record_write(&mut map_scratch, written, point, index);
+            written += 1;
+        });
+
+        // Handing over fewer secondary colors only happens on cancellation.
+        if written < count {
+            drop(map_scratch);
+            remove_file(&path_scratch)?;
+
+            return Err(io::Error::new(ErrorKind::Interrupted, "Cancelled."));
+        }
+
+        // Lay a grid over the bounds with about the right number of cells.
+        let grid = Grid::new(minimum, maximum, count);
+        let count_cells = grid.count_cells;
+
+        // Sort the records into their cells with a counting sort.
+        let mut offsets = vec![0_u64; count_cells.pow(3) + 1];
+
+        for index_record in 0..count {
+            let (point, _) = record_read(&map_scratch, index_record);
+
+            offsets[grid.cell(grid.coordinates(point)) + 1] += 1;
+        }
+        for index_cell in 0..count_cells.pow(3) {
+            offsets[index_cell + 1] += offsets[index_cell];
+        }
+
+        let start = SIZE_HEADER + 8 * offsets.len();
+
+        let mut map = map_new(path, start + count * SIZE_RECORD)?;
+
+        // Write the header and the offsets of the cells.
+        let mut header = Vec::with_capacity(SIZE_HEADER);
+        header.extend_from_slice(MAGIC);
+        for value in [lattice.resolution, lattice.count_primaries, count, count_cells] {
+            header.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        for vector in [grid.minimum, grid.size_cell] {
+            for axis in 0..3 {
+                header.extend_from_slice(&vector[axis].to_le_bytes());
+            }
+        }
+
+        map[..SIZE_HEADER].copy_from_slice(&header);
+        for (index_cell, offset) in offsets.iter().enumerate() {
+            map[SIZE_HEADER + 8 * index_cell..SIZE_HEADER + 8 * (index_cell + 1)].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        let mut cursors = offsets;
+
+        for index_record in 0..count {
+            let (point, index) = record_read(&map_scratch, index_record);
+
+            let cursor = &mut cursors[grid.cell(grid.coordinates(point))];
+            record_write(&mut map[start..], *cursor as usize, point, index);
+            *cursor += 1;
+        }
+
+        drop(map_scratch);
+        remove_file(&path_scratch)?;
+
+        map.flush()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            map: map.make_read_only()?,
+            lattice,
+            grid,
+            start,
+            persistent: false,
+        })
+    }
+
+    /// Opens the store at the given path, which was created by an earlier run
+    /// of the program. The store's file is kept when the store is dropped.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        // Stores are only ever written to before they're persisted, so nothing
+        // is expected to be touching the file anymore.
+        let map = unsafe { Mmap::map(&file) }?;
+
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "not a secondary color store");
+
+        if map.len() < SIZE_HEADER || &map[..8] != MAGIC {
+            return Err(invalid());
+        }
+
+        let value = |position: usize| u64_read(&map, 8 + 8 * position) as usize;
+        let vector = |position: usize| Vector3([
+            f32_read(&map, 40 + 12 * position),
+            f32_read(&map, 44 + 12 * position),
+            f32_read(&map, 48 + 12 * position),
+        ]);
+
+        // Lattices need 0 and 1 among the samples of each ink.
+        if value(0) < 2 {
+            return Err(invalid());
+        }
+
+        let lattice = Lattice::new(value(0), value(1)).ok_or_else(invalid)?;
+        let count = value(2);
+        let count_cells = value(3);
+
+        let grid = Grid {
+            minimum: vector(0),
+            size_cell: vector(1),
+            count_cells,
+        };
+
+        let start = count_cells.checked_pow(3)
+            .and_then(|count_offsets| (count_offsets + 1).checked_mul(8))
+            .and_then(|size_offsets| size_offsets.checked_add(SIZE_HEADER))
+            .ok_or_else(invalid)?;
+
+        if count_cells == 0 || count.checked_mul(SIZE_RECORD).and_then(|size| size.checked_add(start)) != Some(map.len()) {
+            return Err(invalid());
+        }
+
+        // The cells' records must follow each other from the first record to
+        // the last, or searches would read past them.
+        let mut offset_previous = 0;
+        for index_cell in 0..=count_cells.pow(3) {
+            let offset = u64_read(&map, SIZE_HEADER + 8 * index_cell);
+
+            if offset < offset_previous || offset > count as u64 || (index_cell == 0 && offset != 0) {
+                return Err(invalid());
+            }
+
+            offset_previous = offset;
+        }
+        if offset_previous != count as u64 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            map,
+            lattice,
+            grid,
+            start,
+            persistent: true,
+        })
+    }
+
+    /// Moves the store's file to the given path, where it is kept when the
+    /// store is dropped.
+    pub fn persist(&mut self, path: &Path) -> io::Result<()> {
+        rename(&self.path, path)?;
+
+        self.path = path.to_path_buf();
+        self.persistent = true;
+
+        Ok(())
+    }
+
+    /// Returns the offset, in records, of the first record of the cell with
+    /// the given index. The offset of the cell after the last is the number of
+    /// records.
+    fn offset(&self, cell: usize) -> usize {
+        u64_read(&self.map, SIZE_HEADER + 8 * cell) as usize
+    }
+}
+
+impl Search for Store {
+    fn nearest_k(&self, mapping: &Mapping, point: Vector3, count: usize) -> Vec<u64> {
+        self.grid.nearest_k(mapping, point, count, |cell| {
+            (self.offset(cell)..self.offset(cell + 1)).map(|index_record| record_read(&self.map[self.start..], index_record))
+        })
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if !self.persistent {
+            let _ = remove_file(&self.path);
+        }
+    }
+}
+
+/// Creates a file at the given path of the given size in bytes, and maps it
+/// into memory.
+fn map_new(path: &Path, size: usize) -> io::Result<MmapMut> {
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.set_len(size as u64)?;
+
+    // Nothing else is expected to be touching a file this program just
+    // created, which is as much as memory mapping can ask for.
+    unsafe { MmapMut::map_mut(&file) }
+}
+
+/// Writes the given record into the given position of the given memory.
+fn record_write(map: &mut [u8], position: usize, point: Vector3, index: u64) {
+    let record = &mut map[position * SIZE_RECORD..(position + 1) * SIZE_RECORD];
+
+    for axis in 0..3 {
+        record[4 * axis..4 * axis + 4].copy_from_slice(&point[axis].to_le_bytes());
+    }
+    record[12..20].copy_from_slice(&index.to_le_bytes());
+}
+
+/// Reads the record at the given position of the given memory.
+fn record_read(map: &[u8], position: usize) -> (Vector3, u64) {
+    let record = &map[position * SIZE_RECORD..(position + 1) * SIZE_RECORD];
+
+    (
+        Vector3([f32_read(record, 0), f32_read(record, 4), f32_read(record, 8)]),
+        u64_read(record, 12)
+    )
+}
+
+/// Reads the little-endian `f32` at the given offset of the given memory.
+fn f32_read(map: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(map[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads the little-endian `u64` at the given offset of the given memory.
+fn u64_read(map: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(map[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rstar::RTree;
+    use std::env::temp_dir;
+
+    use crate::mapping::WEIGHTS_HUE;
+    use crate::secondaries;
+    use crate::secondaries::Lattice;
+    use crate::testing;
+
+    /// Tests that searching a store agrees with searching an R-tree of the same
+    /// secondary colors, including for points outside of the store's grid.
+    #[test]
+    fn test_store() {
+        let inks = testing::inks(3, 2.0);
+        let lattice = Lattice::new(20, 3).unwrap();
+
+        for mapping in [Mapping::Colorimetric, Mapping::Weighted(WEIGHTS_HUE)] {
+            let secondaries = secondaries::enumerate(&inks, &mapping, lattice, 2);
+            let path = temp_dir().join(format!("test_store_{}.bin", std::process::id()));
+
+            let store = Store::create(&path, lattice, secondaries.len(), |function| {
+                for secondary in secondaries.iter() {
+                    function(*secondary.geom(), secondary.data);
+                }
+            }).unwrap();
+            let rtree = RTree::bulk_load(secondaries);
+
+            for color in [Vector3([0.5, 0.5, 0.5]), Vector3([0.1, 0.05, 0.3]), Vector3([2.0, -1.0, 0.0])] {
+                let point = mapping.point(color, inks.white);
+
+                assert_eq!(rtree.nearest_k(&mapping, point, 3), store.nearest_k(&mapping, point, 3));
+            }
+
+            drop(store);
+
+            assert!(!path.exists());
+        }
+    }
+
+    /// Tests that a persisted store can be reopened, and that other files
+    /// can't be opened as stores.
+    #[test]
+    fn test_store_persist() {
+        let inks = testing::inks(2, 1.5);
+        let lattice = Lattice::new(30, 2).unwrap();
+        let mapping = Mapping::Colorimetric;
+
+        let secondaries = secondaries::enumerate(&inks, &mapping, lattice, 2);
+        let path = temp_dir().join(format!("test_store_persist_{}.bin", std::process::id()));
+        let path_persistent = temp_dir().join(format!("test_store_persist_{}.cache", std::process::id()));
+
+        let mut store = Store::create(&path, lattice, secondaries.len(), |function| {
+            for secondary in secondaries.iter() {
+                function(*secondary.geom(), secondary.data);
+            }
+        }).unwrap();
+        store.persist(&path_persistent).unwrap();
+        drop(store);
+
+        assert!(!path.exists());
+
+        let store = Store::open(&path_persistent).unwrap();
+        let rtree = RTree::bulk_load(secondaries);
+
+        assert_eq!(lattice, store.lattice);
+        for color in [Vector3([0.5, 0.5, 0.5]), Vector3([0.1, 0.05, 0.3])] {
+            let point = mapping.point(color, inks.white);
+
+            assert_eq!(rtree.nearest_k(&mapping, point, 3), store.nearest_k(&mapping, point, 3));
+        }
+
+        drop(store);
+
+        assert!(path_persistent.exists());
+
+        // Offsets that start past the first record or run past the last are
+        // caught.
+        let data = std::fs::read(&path_persistent).unwrap();
+        for (position, offset) in [(SIZE_HEADER, 1), (SIZE_HEADER + 8, u64::MAX)] {
+            let mut data = data.clone();
+            data[position..position + 8].copy_from_slice(&offset.to_le_bytes());
+            std::fs::write(&path_persistent, data).unwrap();
+
+            assert_eq!(ErrorKind::InvalidData, Store::open(&path_persistent).err().unwrap().kind());
+        }
+
+        std::fs::write(&path_persistent, b"SEPSTOR1 but not really").unwrap();
+        assert!(Store::open(&path_persistent).is_err());
+
+        remove_file(&path_persistent).unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+use crate::inks::Inks;
+use crate::vector::Vector3;
+
+/// The color of the paper of the inks that tests mix, the D50 white point.
+pub const WHITE: Vector3 = Vector3([0.9642, 1.0, 0.8249]);
+
+/// The colors of the inks that tests mix, in XYZ space.
+const PRIMARIES: [Vector3; 3] = [
+    Vector3([0.15, 0.2, 0.6]),
+    Vector3([0.5, 0.3, 0.1]),
+    Vector3([0.3, 0.6, 0.2]),
+];
+
+/// Returns the first given number of the inks that tests mix, of which there
+/// are 3, with the given ink limit.
+pub fn inks(count: usize, limit: f32) -> Inks {
+    Inks {
+        white: WHITE,
+        primaries: PRIMARIES[..count].to_vec(),
+        limit,
+    }
+}
@@ -1,4 +1,7 @@
 use rstar::Point;
+use rstar::RTreeNum;
+use std::fmt::Display;
+use std::iter::Sum;
 use std::ops::Add;
 use std::ops::AddAssign;
 use std::ops::Div;
@@ -11,29 +14,100 @@ use std::ops::Neg;
 use std::ops::Sub;
 use std::ops::SubAssign;
 
-/// A three-dimensional vector whose components are f32 values.
+/// The precision that colors are computed with.
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Vector3(pub [f32; 3]);
+pub enum Precision {
+    /// f32 values.
+    Single,
+    /// f64 values.
+    Double,
+}
+
+/// A floating-point type that vectors can be made of.
+pub trait Scalar: RTreeNum + Display + Sum + Send + Sync + 'static {
+    /// Converts the given f32 value.
+    fn from_f32(value: f32) -> Self;
+    /// Converts the given f64 value, rounding it if need be.
+    fn from_f64(value: f64) -> Self;
+    /// Converts the value to f64.
+    fn to_f64(self) -> f64;
+    /// Returns the greater of the value and the given one.
+    fn max(self, other: Self) -> Self;
+    /// Restricts the value to the given interval.
+    fn clamp(self, minimum: Self, maximum: Self) -> Self;
+}
+
+macro_rules! scalar {
+    ($type:ty) => {
+        impl Scalar for $type {
+            fn from_f32(value: f32) -> Self {
+                value as $type
+            }
+
+            fn from_f64(value: f64) -> Self {
+                value as $type
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn max(self, other: Self) -> Self {
+                <$type>::max(self, other)
+            }
+
+            fn clamp(self, minimum: Self, maximum: Self) -> Self {
+                <$type>::clamp(self, minimum, maximum)
+            }
+        }
+
+        // Scalar multiplication is commutative.
+        impl Mul<Vector3<$type>> for $type {
+            type Output = Vector3<$type>;
+
+            fn mul(self, multiplicand: Vector3<$type>) -> Vector3<$type> {
+                let mut multiplicand = multiplicand;
+                multiplicand *= self;
+                multiplicand
+            }
+        }
+    };
+}
+
+scalar!(f32);
+scalar!(f64);
+
+/// A three-dimensional vector whose components are f32 values, unless
+/// otherwise specified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vector3<T = f32>(pub [T; 3]);
+
+impl<T: Scalar> Vector3<T> {
+    /// Converts the vector's components to another scalar type.
+    pub fn cast<U: Scalar>(self) -> Vector3<U> {
+        Vector3(self.0.map(|component| U::from_f64(component.to_f64())))
+    }
+}
 
 // Element access.
 
-impl Index<usize> for Vector3 {
-    type Output = f32;
+impl<T: Scalar> Index<usize> for Vector3<T> {
+    type Output = T;
 
-    fn index(&self, index: usize) -> &f32 {
+    fn index(&self, index: usize) -> &T {
         &self.0[index]
     }
 }
 
-impl IndexMut<usize> for Vector3 {
-    fn index_mut(&mut self, index: usize) -> &mut f32 {
+impl<T: Scalar> IndexMut<usize> for Vector3<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
         &mut self.0[index]
     }
 }
 
 // Negation (additive inversion).
 
-impl Neg for Vector3 {
+impl<T: Scalar> Neg for Vector3<T> {
     type Output = Self;
 
     fn neg(self) -> Self {
@@ -47,7 +121,7 @@ impl Neg for Vector3 {
 
 // Vector (element-wise) addition.
 
-impl AddAssign for Vector3 {
+impl<T: Scalar> AddAssign for Vector3<T> {
     fn add_assign(&mut self, other: Self) {
         *self = Self([
             self[0] + other[0],
@@ -57,7 +131,7 @@ impl AddAssign for Vector3 {
     }
 }
 
-impl Add for Vector3 {
+impl<T: Scalar> Add for Vector3<T> {
     type Output = Self;
 
     fn add(self, addend: Self) -> Self {
@@ -67,7 +141,7 @@ impl Add for Vector3 {
     }
 }
 
-impl SubAssign for Vector3 {
+impl<T: Scalar> SubAssign for Vector3<T> {
     fn sub_assign(&mut self, other: Self) {
         *self = Self([
             self[0] - other[0],
@@ -77,7 +151,7 @@ impl SubAssign for Vector3 {
     }
 }
 
-impl Sub for Vector3 {
+impl<T: Scalar> Sub for Vector3<T> {
     type Output = Self;
 
     fn sub(self, addend: Self) -> Self {
@@ -89,8 +163,8 @@ impl Sub for Vector3 {
 
 // Scalar multiplication.
 
-impl MulAssign<f32> for Vector3 {
-    fn mul_assign(&mut self, other: f32) {
+impl<T: Scalar> MulAssign<T> for Vector3<T> {
+    fn mul_assign(&mut self, other: T) {
         *self = Self([
             other * self[0],
             other * self[1],
@@ -99,29 +173,18 @@ impl MulAssign<f32> for Vector3 {
     }
 }
 
-impl Mul<f32> for Vector3 {
+impl<T: Scalar> Mul<T> for Vector3<T> {
     type Output = Self;
 
-    fn mul(self, multiplicand: f32) -> Self {
+    fn mul(self, multiplicand: T) -> Self {
         let mut multiplier = self;
         multiplier *= multiplicand;
         multiplier
     }
 }
 
-// Scalar multiplication is commutative.
-impl Mul<Vector3> for f32 {
-    type Output = Vector3;
-
-    fn mul(self, multiplicand: Vector3) -> Vector3 {
-        let mut multiplicand = multiplicand;
-        multiplicand *= self;
-        multiplicand
-    }
-}
-
-impl DivAssign<f32> for Vector3 {
-    fn div_assign(&mut self, other: f32) {
+impl<T: Scalar> DivAssign<T> for Vector3<T> {
+    fn div_assign(&mut self, other: T) {
         *self = Self([
             self[0] / other,
             self[1] / other,
@@ -130,10 +193,10 @@ impl DivAssign<f32> for Vector3 {
     }
 }
 
-impl Div<f32> for Vector3 {
+impl<T: Scalar> Div<T> for Vector3<T> {
     type Output = Self;
 
-    fn div(self, denominator: f32) -> Self {
+    fn div(self, denominator: T) -> Self {
         let mut numerator = self;
         numerator /= denominator;
         numerator
@@ -142,7 +205,7 @@ impl Div<f32> for Vector3 {
 
 // Hadamard (element-wise) multiplication.
 
-impl MulAssign for Vector3 {
+impl<T: Scalar> MulAssign for Vector3<T> {
     fn mul_assign(&mut self, other: Self) {
         *self = Self([
             self[0] * other[0],
@@ -152,7 +215,7 @@ impl MulAssign for Vector3 {
     }
 }
 
-impl Mul for Vector3 {
+impl<T: Scalar> Mul for Vector3<T> {
     type Output = Self;
 
     fn mul(self, multiplicand: Self) -> Self {
@@ -162,7 +225,7 @@ impl Mul for Vector3 {
     }
 }
 
-impl DivAssign for Vector3 {
+impl<T: Scalar> DivAssign for Vector3<T> {
     fn div_assign(&mut self, other: Self) {
         *self = Self([
             self[0] / other[0],
@@ -172,7 +235,7 @@ impl DivAssign for Vector3 {
     }
 }
 
-impl Div for Vector3 {
+impl<T: Scalar> Div for Vector3<T> {
     type Output = Self;
 
     fn div(self, denominator: Self) -> Self {
@@ -184,8 +247,8 @@ impl Div for Vector3 {
 
 // Interoperability with rtree.
 
-impl Point for Vector3 {
-    type Scalar = f32;
+impl<T: Scalar> Point for Vector3<T> {
+    type Scalar = T;
     const DIMENSIONS: usize = 3;
 
     fn generate(mut generator: impl FnMut(usize) -> Self::Scalar) -> Self {
@@ -196,11 +259,11 @@ impl Point for Vector3 {
         ])
     }
 
-    fn nth(&self, index: usize) -> f32 {
+    fn nth(&self, index: usize) -> T {
         self[index]
     }
 
-    fn nth_mut(&mut self, index: usize) -> &mut f32 {
+    fn nth_mut(&mut self, index: usize) -> &mut T {
         &mut self[index]
     }
 }
@@ -226,7 +289,7 @@ mod tests {
     ; "vector_addition_inverse")]
     #[test_case(
         Vector3([2.0, 2.0, 2.0]),
-        2.0 * Vector3([1.0, 1.0, 1.0])
+        2.0_f32 * Vector3([1.0, 1.0, 1.0])
     ; "scalar_multiplication")]
     #[test_case(
         Vector3([2.0, 2.0, 2.0]),
@@ -1,5 +1,7 @@
 use lazy_static::lazy_static;
 use std::fs::File;
+use std::fs::metadata;
+use std::fs::remove_dir_all;
 use std::fs::remove_file;
 use std::io::BufRead;
 use std::io::BufReader;
@@ -7,8 +9,11 @@ use std::panic::catch_unwind;
 use std::panic::UnwindSafe;
 use std::path::Path;
 use std::process::Command;
+use std::process::Stdio;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
 use test_case::test_case;
 
 // It is unbelievably annoying how Rust's testing system doesn't have setup or
@@ -20,11 +25,18 @@ lazy_static! {
 
 /// Tears down the post-test-execution state.
 pub fn teardown() {
-    let _ = remove_file("tests/output.cube");
+    let _ = remove_file("tests/store.bin");
+    let _ = remove_dir_all("tests/cache");
 
-    for index in 0..=10 {
-        let _ = remove_file(format!("tests/output_{}.cube", index));
-        let _ = remove_file(format!("tests/output_{}m.cube", index));
+    for stem in ["tests/output", "tests/output_f64", "tests/output_2pt", "tests/output_3pt", "tests/output_4pt", "tests/output_9pt"] {
+        for extension in ["cube", "3dl", "clf", "csp", "vf", "png", "glsl", "hlsl", "wgsl", "icc"] {
+            let _ = remove_file(format!("{}.{}", stem, extension));
+
+            for index in 0..=10 {
+                let _ = remove_file(format!("{}_{}.{}", stem, index, extension));
+                let _ = remove_file(format!("{}_{}m.{}", stem, index, extension));
+            }
+        }
     }
 }
 
@@ -58,6 +70,63 @@ pub fn run(test: impl FnOnce() -> () + UnwindSafe) {
 #[test_case("-p AdobeRGB1998 -o tests/output.cube -c 1 2 3 -s 2 -t 1")]
 #[test_case("-p aDObErgB1998 -o tests/output.cube -c 1 2 3 -s 2 -t 1" ; "profile_case_insensitive")]
 #[test_case("-p Rec709 -o tests/output.cube -c 1 2 3 -s 2 -t 1")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2,3 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 3,2,3 -t 1 --adaptive" ; "size_list_adaptive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 -l 0.5")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 -l 0")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 -m lab")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 -m lightness")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 -m HUE" ; "mapping_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 -w 1 2 3")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 -m hue -w 1 2 3")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 --adaptive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 -l 1 -m hue --adaptive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 --store tests/store.bin")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 -m lab --adaptive --store tests/store.bin")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 --cache tests/cache")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 -m lab --adaptive --cache tests/cache")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 --tolerance 20")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -l 1 --tolerance 20 --statistic P95" ; "statistic_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 --blend idw")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 100 --blend Barycentric --neighbors 8" ; "blend_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 1 --blend idw --neighbors 100" ; "blend_few_secondaries")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --smooth 2")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2,3 -t 100 -l 1 --smooth 0 --solve" ; "smooth_solve")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --optimize trilinear")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2,3 -t 100 --smooth 1 --optimize Tetrahedral" ; "optimize_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --search grid")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --search KdTree" ; "search_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -m hue --search table --blend idw" ; "search_table_blend")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 1 --search rtree" ; "search_rtree_few_secondaries")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --threads 1")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --threads 64 --adaptive" ; "threads_many_adaptive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --adaptive --optimize tetrahedral" ; "adaptive_optimize")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --quiet")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -Q --adaptive --store tests/store.bin" ; "quiet_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 --precision f64")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2,3 -t 100 --precision F64 --smooth 1 --optimize tetrahedral --solve" ; "precision_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 1 -f cube")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 1 --format CUBE" ; "format_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.3dl -c 1 2 3 -c 4 5 6 -s 2,3 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -f 3DL --depth 16" ; "format_3dl_depth")]
+#[test_case("-p sRGB -o tests/output.clf -c 1 2 3 -c 4 5 6 -s 2,3 -t 100 -l 1.5")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 --tolerance 20 -f CLF --shaper --optimize tetrahedral" ; "format_clf_shaper")]
+#[test_case("-p sRGB -o tests/output.csp -c 1 2 3 -c 4 5 6 -s 2,3 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -f CSP --precision f64" ; "format_csp_double")]
+#[test_case("-p sRGB -o tests/output.vf -c 1 2 3 -c 4 5 6 -s 2,3 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -f VF" ; "format_vf_case_insensitive")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -c 4 5 6 -s 4,9 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 4 -t 100 -f Hald --depth 16" ; "format_hald_depth")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -c 4 5 6 -s 4 -t 100 -f strip")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -c 4 5 6 -s 4 -t 100 -f Strip --layout Vertical --flip --depth 16" ; "format_strip_vertical_flip")]
+#[test_case("-p sRGB -o tests/output.glsl -c 1 2 3 -c 4 5 6 -s 3,4 -t 100")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100 -f HLSL" ; "format_hlsl")]
+#[test_case("-p sRGB -o tests/output.wgsl -c 1 2 3 -c 4 5 6 -s 3 -t 100 --optimize tetrahedral")]
+#[test_case("-p sRGB -o tests/output.icc -c 1 2 3 -c 4 5 6 -s 3,4 -t 100")]
+#[test_case("-p sRGB -o tests/output.icc -c 1 2 3 -c 4 5 6 -s 3 -t 100 --abstract")]
+#[test_case("-p sRGB -o tests/output.icc -c 0 255 255 -c 255 0 255 -c 255 255 0 -s 3,4 -t 100 -f nclr")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 1 --solve")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 2 -t 1 -l 1 -m lab --solve")]
 pub fn test_success(arguments: &str) {
     run(|| {
         let mut process = Command::new("cargo");
@@ -88,10 +157,64 @@ pub fn test_success(arguments: &str) {
 #[test_case("-p sRGB -o tests/output.cube -c not_a_number 2 3"           ; "primary_not_number")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s not_a_number"      ; "size_not_number")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 1"                 ; "size_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2,1"               ; "size_list_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -s 2,"                ; "size_list_empty")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -t not_a_number"      ; "target_not_number")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -t 0"                 ; "target_illegal")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -l not_a_number"      ; "inklimit_not_number")]
 #[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -l -0.5"              ; "inklimit_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --store"              ; "store_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --cache"              ; "cache_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --blend"              ; "blend_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --blend nearest"      ; "blend_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --blend idw --neighbors 0" ; "neighbors_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --blend barycentric --neighbors 17" ; "neighbors_barycentric")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --neighbors 4"        ; "neighbors_without_blend")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --smooth"             ; "smooth_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --smooth -1"          ; "smooth_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --smooth 1 --blend idw" ; "smooth_blend")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --optimize"           ; "optimize_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --optimize cubic"     ; "optimize_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --search"             ; "search_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --search octree"      ; "search_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --search grid --store tests/output.bin" ; "search_store")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --search grid --cache tests/cache" ; "search_cache")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --store tests/store.bin --cache tests/cache" ; "store_cache")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --threads"            ; "threads_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --threads 0"          ; "threads_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --threads all"        ; "threads_not_number")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --format"             ; "format_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --format txt"         ; "format_not_found")]
+#[test_case("-p sRGB -o tests/output.3dl -c 1 2 3 --depth"               ; "depth_missing_argument")]
+#[test_case("-p sRGB -o tests/output.3dl -c 1 2 3 --depth 8"             ; "depth_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --depth 12"           ; "depth_cube")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --shaper"             ; "shaper_cube")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -s 4,10"               ; "hald_size_not_square")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -s 4 --depth 12"       ; "hald_depth_illegal")]
+#[test_case("-p sRGB -o tests/output.3dl -c 1 2 3 --depth no"            ; "depth_not_number")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -f strip --depth 10"   ; "strip_depth_illegal")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 -f strip --layout grid" ; "strip_layout_unknown")]
+#[test_case("-p sRGB -o tests/output.png -c 1 2 3 --layout vertical"     ; "layout_hald")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --flip"               ; "flip_cube")]
+#[test_case("-p sRGB -o tests/output.glsl -c 1 2 3 --depth 8"            ; "depth_glsl")]
+#[test_case("-p sRGB -o tests/output.glsl -c 1 2 3 -s 17,34"             ; "size_glsl")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --abstract"           ; "abstract_cube")]
+#[test_case("-p sRGB -o tests/output.icc -c 1 2 3 -f nclr"               ; "nclr_one_primary")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --precision"          ; "precision_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --precision f16"      ; "precision_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --tolerance"          ; "tolerance_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --tolerance no"       ; "tolerance_not_number")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --tolerance 0"        ; "tolerance_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -t 10 --tolerance 1"  ; "tolerance_target")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --statistic p95"      ; "statistic_without_tolerance")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 --tolerance 1 --statistic p50" ; "statistic_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -l 0.5 -t 18446744073709551615" ; "target_too_large")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -m"                   ; "mapping_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -m no_such_mapping"   ; "mapping_not_found")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -w 1 2"               ; "weights_missing_argument")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -w 1 2 not_a_number"  ; "weights_not_number")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -w 1 0 1"             ; "weights_illegal")]
+#[test_case("-p sRGB -o tests/output.cube -c 1 2 3 -m colorimetric -w 1 1 1" ; "weights_colorimetric")]
 pub fn test_bad_arguments(arguments: &str) {
     run(|| {
         let mut process = Command::new("cargo");
@@ -104,6 +227,135 @@ pub fn test_bad_arguments(arguments: &str) {
     });
 }
 
+/// Tests that the output files are deleted when the program fails after
+/// creating them.
+#[test]
+pub fn test_failure() {
+    run(|| {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_separations"));
+        process.args("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1 --cache tests/output.cube/cache".split(' '));
+
+        let output = process.output().unwrap();
+
+        assert_eq!(Some(1), output.status.code());
+        for path in ["tests/output.cube", "tests/output_0.cube", "tests/output_0m.cube"] {
+            assert!(!Path::new(path).exists());
+        }
+    });
+}
+
+/// Tests that the number of threads can be given by the environment, unless
+/// the command line gives one.
+#[allow(clippy::unused_unit)]
+#[test_case("2", "", true                 ; "variable")]
+#[test_case("0", "", false                ; "variable_illegal")]
+#[test_case("0", " --threads 2", true     ; "variable_overridden")]
+pub fn test_threads_variable(value: &str, arguments: &str, success: bool) {
+    run(|| {
+        let mut process = Command::new("cargo");
+        process.args(["run", "--"]);
+        process.args(format!("-p sRGB -o tests/output.cube -c 1 2 3 -s 2 -t 1{}", arguments).split(' '));
+        process.env("SEPARATIONS_THREADS", value);
+
+        let output = process.output().unwrap();
+
+        assert_eq!(success, output.status.success());
+    });
+}
+
+/// Tests that progress is reported on the standard error stream, unless the
+/// program is told to be quiet.
+#[allow(clippy::unused_unit)]
+#[test_case("", true             ; "reported")]
+#[test_case(" --quiet", false    ; "quiet")]
+#[test_case(" -q --optimize tetrahedral --solve", false ; "quiet_every_phase")]
+pub fn test_progress(arguments: &str, reported: bool) {
+    run(|| {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_separations"));
+        process.args(format!("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -s 3 -t 100{}", arguments).split(' '));
+
+        let output = process.output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(reported, !output.stderr.is_empty());
+    });
+}
+
+/// Tests that interrupting the program once the given phase has started stops
+/// it before the phase finishes, without leaving any output files or stores
+/// behind.
+#[cfg(unix)]
+#[allow(clippy::unused_unit)]
+#[test_case("-t 100000000", "Generating secondary colors", false                        ; "generating")]
+#[test_case("-t 100000000 --store tests/store.bin", "Generating secondary colors", false ; "storing")]
+#[test_case("-t 1000 -s 256", "Writing 3D LUTs", true                                   ; "writing")]
+pub fn test_interrupt(arguments: &str, phase: &str, written: bool) {
+    run(|| {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_separations"));
+        process.args("-p sRGB -o tests/output.cube -c 1 2 3 -c 4 5 6 -c 7 8 9".split(' '));
+        process.args(arguments.split(' '));
+        process.stderr(Stdio::piped());
+
+        let mut child = process.spawn().unwrap();
+        let mut lines = BufReader::new(child.stderr.take().unwrap()).lines().map(Result::unwrap);
+
+        // Progress written to anything but a terminal shows phases as they
+        // start and as they finish.
+        let started = format!("{}\x1B[0m: 0/", phase);
+        assert!(lines.any(|line| line.contains(&started)), "{} never started", phase);
+
+        // Wait for more to be written than the outputs buffer, so that the
+        // files are partly written by the time they're interrupted.
+        while written && metadata("tests/output.cube").map_or(0, |metadata| metadata.len()) <= 1 << 16 {
+            sleep(Duration::from_millis(10));
+        }
+
+        Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+
+        let finished = format!("{}\x1B[0m: ", phase);
+        assert!(!lines.any(|line| line.contains(&finished) && line.contains(" in ")), "{} finished", phase);
+
+        assert_eq!(Some(130), child.wait().unwrap().code());
+        for path in ["tests/output.cube", "tests/output_0.cube", "tests/output_0m.cube", "tests/store.bin", "tests/store.bin.unsorted"] {
+            assert!(!Path::new(path).exists());
+        }
+    });
+}
+
+/// Tests that double precision changes the colors written out, but only by
+/// about as much as single precision rounds them.
+#[test]
+pub fn test_precision() {
+    run(|| {
+        for (precision, path) in [("f32", "tests/output.cube"), ("f64", "tests/output_f64.cube")] {
+            let mut process = Command::new(env!("CARGO_BIN_EXE_separations"));
+            process.args("-p sRGB -c 1 2 3 -c 4 5 6 -s 5 -t 10000 -q".split(' '));
+            process.args(["-o", path, "--precision", precision]);
+
+            assert!(process.status().unwrap().success());
+        }
+
+        /// Parses the colors of the 3D LUT at the given path.
+        fn parse_colors(path: &str) -> Vec<f64> {
+            BufReader::new(File::open(path).unwrap()).lines().map(Result::unwrap)
+                .filter_map(|line| line.split(' ').map(str::parse::<f64>).collect::<Result<Vec<_>, _>>().ok())
+                .filter(|components| components.len() == 3)
+                .flatten()
+                .collect()
+        }
+
+        for (path_single, path_double) in [("tests/output.cube", "tests/output_f64.cube"), ("tests/output_0.cube", "tests/output_f64_0.cube")] {
+            let single = parse_colors(path_single);
+            let double = parse_colors(path_double);
+
+            assert_eq!(3 * 5_usize.pow(3), single.len());
+            assert_eq!(single.len(), double.len());
+            assert!(single.iter().zip(&double).any(|(single, double)| single != double), "{}", path_double);
+            assert!(single.iter().zip(&double).all(|(single, double)| (single - double).abs() <= 1e-5), "{}", path_double);
+        }
+    });
+}
+
 /// Tests that the program's output (with the given inputs) is identical, within
 /// a given tolerance, to a given reference output.
 #[allow(clippy::unused_unit)]
@@ -139,6 +391,10 @@ pub fn test_bad_arguments(arguments: &str) {
 // ; "cmyk")]
 #[ignore]
 pub fn test_output(arguments: &str, comparisons: Vec<(&str, &str)>, tolerance: f32) {
+    // The output test predates lints against needless conversions. It allows
+    // them around its whole body, since `test_case` drops the attributes of
+    // the function itself.
+    #[allow(clippy::useless_conversion)]
     run(|| {
         let mut process = Command::new("cargo");
         process.args(["run", "--", "-o", "tests/output.cube"]);